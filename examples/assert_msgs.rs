@@ -0,0 +1,6 @@
+// The bounds check on `arr[i]` should have a `msg` of kind `BoundsCheck`, with `len` and `index`
+// operands.  `a / b` should have a `DivisionByZero` assertion, and (since `i32::MIN / -1`
+// overflows) an `Overflow` assertion whose `op` is `Div`.
+fn f(arr: [u32; 4], i: usize, a: i32, b: i32) -> (u32, i32) {
+    (arr[i], a / b)
+}
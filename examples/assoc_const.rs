@@ -0,0 +1,20 @@
+// In the `consts` of the impls, `Foo::N` should have an evaluated `value` of 8, while
+// `Wrap::<T>::N` is generic and gets `"value": null`.
+use std::mem::size_of;
+
+pub struct Foo(u64);
+
+impl Foo {
+    pub const N: usize = size_of::<Self>();
+}
+
+pub struct Wrap<T>(T);
+
+impl<T> Wrap<T> {
+    pub const N: usize = size_of::<T>();
+}
+
+fn main() {
+    assert!(Foo::N == 8);
+    assert!(Wrap::<u16>::N == 2);
+}
@@ -0,0 +1,22 @@
+// `Item` is both an associated type and a method of `Trait`.  In the impl's items, the type
+// `<S as Trait>::Item` should have `implements` pointing at the trait's associated type, and the
+// method `<S as Trait>::Item` at the trait's method.
+#![allow(non_snake_case)]
+
+pub trait Trait {
+    type Item;
+    fn Item(&self) -> Self::Item;
+}
+
+pub struct S;
+
+impl Trait for S {
+    type Item = u32;
+    fn Item(&self) -> u32 {
+        1
+    }
+}
+
+pub fn f(s: &S) -> u32 {
+    s.Item()
+}
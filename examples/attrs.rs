@@ -0,0 +1,12 @@
+#![feature(custom_attribute)]
+
+#[crux_test]
+#[rustfmt::skip]
+fn crux_test() {
+    assert!(1 + 1 == 2);
+}
+
+#[no_mangle]
+pub extern "C" fn exported() -> u32 { 1 }
+
+fn main() {}
@@ -0,0 +1,17 @@
+// `M` and `P` are too large for a scalar, so `rustc` evaluates them to `ConstValue::ByRef`.  Their
+// `Const` values should have an `array_val` with eight `int_val` elements, and an `adt_val` whose
+// fields are an `int_val` and a `tuple_val`.
+const M: [u32; 8] = [1, 2, 3, 4, 5, 6, 7, 8];
+
+struct Point {
+    x: u64,
+    rest: (u64, bool),
+}
+
+const P: Point = Point { x: 1, rest: (2, true) };
+
+fn f() -> u32 {
+    let m = M;
+    let p = P;
+    m[3] + p.x as u32
+}
@@ -0,0 +1,13 @@
+// `call_kind` should be `RustCall` for the closure body, `Intrinsic` (named `size_of`) for
+// `std::intrinsics::size_of`, and `Foreign` with `"abi": "C"` for `double`.
+#![feature(core_intrinsics)]
+
+extern "C" fn double(x: u32) -> u32 {
+    x * 2
+}
+
+fn main() {
+    let add = |a: u32, b: u32| a + b;
+    let n = unsafe { std::intrinsics::size_of::<u64>() };
+    assert!(add(double(1), n as u32) == 10);
+}
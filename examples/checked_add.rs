@@ -0,0 +1,9 @@
+// In a debug build, `a + b` lowers to a `CheckedBinaryOp` with `result_ty` `(u32, bool)`, followed
+// by an overflow `Assert`.
+fn add(a: u32, b: u32) -> u32 {
+    a + b
+}
+
+fn main() {
+    assert!(add(1, 2) == 3);
+}
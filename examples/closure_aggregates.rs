@@ -0,0 +1,21 @@
+// The `Aggregate` rvalue that builds `f` should be a `Closure` with two `ops` and two matching
+// `upvar_tys`, which are `&u32` and `&mut u64`.  The one that builds `gen` should be a
+// `Generator` with `yield_ty` `u32`, `return_ty` `()`, and one upvar, `x`.
+#![feature(generators, generator_trait)]
+
+use std::ops::Generator;
+use std::pin::Pin;
+
+fn main() {
+    let x = 1_u32;
+    let mut y = 2_u64;
+    let mut f = || {
+        y += x as u64;
+    };
+    f();
+
+    let mut gen = move || {
+        yield x;
+    };
+    let _ = Pin::new(&mut gen).resume();
+}
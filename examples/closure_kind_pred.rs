@@ -0,0 +1,12 @@
+// The bound `F: Fn()` on `apply` is a `trait_pred` for `Fn`, not a closure-kind predicate.
+// `rustc` only creates `ClosureKind` predicates while type-checking closures, so they rarely show
+// up in `predicates`.  When one does, it's emitted as a `closure_kind` object with the closure's
+// `def_id` and a `kind` of `Fn`, `FnMut`, or `FnOnce`, instead of `"unknown_pred"`.
+fn apply<F: Fn()>(f: F) {
+    f()
+}
+
+fn main() {
+    let x = 1;
+    apply(|| { let _ = x; });
+}
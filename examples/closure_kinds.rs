@@ -0,0 +1,13 @@
+fn main() {
+    let a = 1_u8;
+    let mut b = 2_u8;
+    let c = String::from("c");
+
+    let read = || a + 1;
+    let mut write = || b += 1;
+    let consume = move || c;
+
+    read();
+    write();
+    consume();
+}
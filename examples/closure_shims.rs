@@ -0,0 +1,11 @@
+fn call_fn<F: Fn(u8) -> u8>(f: &F) -> u8 { f(1) }
+fn call_fn_mut<F: FnMut(u8) -> u8>(f: &mut F) -> u8 { f(2) }
+fn call_fn_once<F: FnOnce(u8) -> u8>(f: F) -> u8 { f(3) }
+
+fn main() {
+    let y = 10;
+    let mut f = |x: u8| x + y;
+    call_fn(&f);
+    call_fn_mut(&mut f);
+    call_fn_once(f);
+}
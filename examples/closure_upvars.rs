@@ -0,0 +1,12 @@
+fn main() {
+    let a = 1_u8;
+    let mut b = String::from("x");
+    let c = vec![1_u32];
+    let mut f = || {
+        b.push('y');
+        a as usize + c.len()
+    };
+    f();
+    let g = move || c.len();
+    g();
+}
@@ -0,0 +1,17 @@
+// `add` should appear in `fns` with `"is_const": true` and `"is_const_fn": true`, called both
+// from `f` and from the initializer of `SUM`.  `unused_at_runtime` is only called from `DOUBLE`'s
+// initializer, but its body should still be exported.
+const fn add(a: u32, b: u32) -> u32 {
+    a + b
+}
+
+const fn unused_at_runtime(a: u32) -> u32 {
+    a * 2
+}
+
+pub const SUM: u32 = add(1, 2);
+pub const DOUBLE: u32 = unused_at_runtime(21);
+
+pub fn f(x: u32) -> u32 {
+    add(x, SUM)
+}
@@ -0,0 +1,11 @@
+// The generic signature of `first` mentions `[u8; N]`, whose `size` should be the symbolic `N`
+// (with `param`) and `len` null.  The instance used by `main` has `len` 3.
+#![feature(const_generics)]
+
+fn first<const N: usize>(a: [u8; N]) -> u8 {
+    a[0]
+}
+
+fn main() {
+    assert!(first([7, 8, 9]) == 7);
+}
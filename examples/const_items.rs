@@ -0,0 +1,17 @@
+// Both items should appear in the `statics` table with their evaluated `value`, and `TABLE`
+// should be marked `"const": true`.
+static mut COUNTER: u32 = 7;
+const TABLE: [u8; 4] = [1, 2, 3, 4];
+
+fn bump() -> u32 {
+    unsafe {
+        COUNTER += 1;
+        COUNTER
+    }
+}
+
+fn main() {
+    let t = TABLE;
+    assert!(bump() == 8);
+    assert!(t[3] == 4);
+}
@@ -0,0 +1,13 @@
+// Put this in `src/lib.rs` of a new crate and run `cargo crux-test` there, with
+// `CRUX_RUST_LIBRARY_PATH` set.  Cargo should build a script in place of the test binary, run it,
+// and report that `add_is_commutative` passed.
+#![feature(custom_attribute)]
+
+pub fn add(a: u8, b: u8) -> u8 {
+    a.wrapping_add(b)
+}
+
+#[crux_test]
+fn add_is_commutative() {
+    assert_eq!(add(200, 100), add(100, 200));
+}
@@ -0,0 +1,21 @@
+// `Greet::name` should have `"has_default_body": false`, and `Greet::greeting` should have
+// `"has_default_body": true` along with its `default_body`.
+pub trait Greet {
+    fn name(&self) -> u32;
+    fn greeting(&self) -> u32 {
+        self.name() + 1
+    }
+}
+
+struct World;
+
+impl Greet for World {
+    fn name(&self) -> u32 {
+        41
+    }
+}
+
+fn main() {
+    let g: &dyn Greet = &World;
+    assert!(g.greeting() == 42);
+}
@@ -0,0 +1,13 @@
+// Reading `x` projects `opt` through `Downcast(1)` (to `Some`) and then `Field(0)`.  Each
+// projection should carry its `result_ty`: `Option<u32>` for the downcast, and `u32` for the
+// field.
+fn get(opt: Option<u32>) -> u32 {
+    match opt {
+        Some(x) => x,
+        None => 0,
+    }
+}
+
+fn main() {
+    assert!(get(Some(3)) == 3);
+}
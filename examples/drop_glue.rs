@@ -0,0 +1,4 @@
+fn main() {
+    let x = (String::from("hello"), 1_u32, Box::new(2_u8));
+    drop(x);
+}
@@ -0,0 +1,15 @@
+// `Noisy` has a `Drop` impl, so dropping `n` calls `drop_in_place::<Noisy>`.  That instance's
+// `intrinsics` entry should have `"instance_kind": "DropGlue"`, and the entry for `main` should
+// have `"instance_kind": "Item"`.
+struct Noisy(u32);
+
+impl Drop for Noisy {
+    fn drop(&mut self) {
+        self.0 = 0;
+    }
+}
+
+fn main() {
+    let n = Noisy(1);
+    let _ = n.0;
+}
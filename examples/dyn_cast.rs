@@ -0,0 +1,16 @@
+trait Foo {
+    fn foo(&self) -> u8;
+}
+
+impl Foo for u8 {
+    fn foo(&self) -> u8 { *self }
+}
+
+fn drop_send(x: &(dyn Foo + Send)) -> &dyn Foo {
+    x
+}
+
+fn main() {
+    let x = 1_u8;
+    drop_send(&x).foo();
+}
@@ -12,3 +12,5 @@ fn j(s: &dyn Send) {}
 
 auto trait Foo {}
 fn testauto(s: &(dyn Send + Foo)) {}
+
+fn k(x: &(dyn Iterator<Item=u32> + Send)) {}
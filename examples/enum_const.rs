@@ -0,0 +1,23 @@
+// `COLOR` should render with `"enum_val": {"variant": 2}`, and the elements of `PALETTE` with
+// variants 0 and 2.  `Signed` uses negative discriminants, and `S` should have variant 0.
+#[derive(Clone, Copy)]
+enum Color {
+    Red,
+    Green,
+    Blue = 7,
+}
+
+#[derive(Clone, Copy)]
+#[repr(i16)]
+enum Signed {
+    Neg = -3,
+    Pos = 3,
+}
+
+const COLOR: Color = Color::Blue;
+const PALETTE: [Color; 2] = [Color::Red, Color::Blue];
+const S: Signed = Signed::Neg;
+
+fn f() -> (Color, [Color; 2], Signed) {
+    (COLOR, PALETTE, S)
+}
@@ -0,0 +1,24 @@
+// `mir-json --export-item double export_item.rs` (or `MIR_JSON_EXPORT_ITEM=double`) should print a
+// crate object whose `fns` table holds only `double`, whose `adts` table holds only `Pair`, and
+// whose only root is `double`.  `triple`, `Unused`, and `main` should not appear, and no
+// `export_item.mir` should be written.
+struct Pair {
+    a: u32,
+    b: u32,
+}
+
+struct Unused(u8);
+
+fn triple(x: u32) -> u32 {
+    x * 3
+}
+
+pub fn double(p: Pair) -> u32 {
+    p.a * 2 + p.b * 2
+}
+
+fn main() {
+    double(Pair { a: 1, b: 2 });
+    triple(4);
+    let _ = Unused(0);
+}
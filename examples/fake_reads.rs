@@ -0,0 +1,10 @@
+// Building MIR for the `match` in `f` produces `FakeRead` statements for the scrutinee.  Those
+// are replaced by `Nop`s before optimized MIR is built, but each block's `data` should still have
+// exactly as many statements as the block has in `rustc`'s MIR dump (`-Z dump-mir=f`).
+pub fn f(x: Option<u32>) -> u32 {
+    match x {
+        Some(y) if y > 2 => y,
+        Some(_) => 1,
+        None => 0,
+    }
+}
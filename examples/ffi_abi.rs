@@ -0,0 +1,27 @@
+// Both functions should get an `fn_abi` in their `intrinsics` entry, with `BigStruct` passed and
+// returned `Indirect`.  `external` is only declared, so it has no `fns` entry.
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct BigStruct {
+    a: u64,
+    b: u64,
+    c: u64,
+    d: u64,
+}
+
+pub extern "C" fn swap_ends(x: BigStruct) -> BigStruct {
+    BigStruct { a: x.d, b: x.b, c: x.c, d: x.a }
+}
+
+extern "C" {
+    fn external(x: BigStruct) -> BigStruct;
+}
+
+fn main() {
+    let x = BigStruct { a: 1, b: 2, c: 3, d: 4 };
+    let y = swap_ends(x);
+    assert!(y.a == 4);
+    if y.b == 0 {
+        unsafe { external(y); }
+    }
+}
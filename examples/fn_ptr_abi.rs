@@ -0,0 +1,15 @@
+// In the ADT entry for `Callbacks`, the `FnPtr` signature of `c` should have
+// `"abi": {"kind": "C"}` and `"unsafety": "Unsafe"`, and the signature of `rust` should have
+// `"abi": {"kind": "Rust"}` and `"unsafety": "Normal"`.
+struct Callbacks {
+    c: unsafe extern "C" fn(i32) -> i32,
+    rust: fn(i32) -> i32,
+}
+
+unsafe extern "C" fn c_impl(x: i32) -> i32 { x }
+fn rust_impl(x: i32) -> i32 { x }
+
+fn f() -> i32 {
+    let cb = Callbacks { c: c_impl, rust: rust_impl };
+    unsafe { (cb.c)(1) + (cb.rust)(2) }
+}
@@ -0,0 +1,17 @@
+unsafe extern "C" fn raw_add(x: u32, y: u32) -> u32 { x + y }
+
+const fn double(x: u32) -> u32 { x * 2 }
+
+fn main() {
+    let f: unsafe extern "C" fn(u32, u32) -> u32 = raw_add;
+    unsafe { f(1, 2) };
+    double(3);
+}
+
+extern "C" {
+    fn printf(fmt: *const u8, ...) -> i32;
+}
+
+pub fn call_printf() {
+    unsafe { printf(b"%d\n\0".as_ptr(), 1_i32) };
+}
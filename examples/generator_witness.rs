@@ -0,0 +1,16 @@
+// The generator's witness type should list `String` in its `tys`, since `s` is live across the
+// `yield`.
+#![feature(generators, generator_trait)]
+
+use std::ops::Generator;
+use std::pin::Pin;
+
+fn main() {
+    let mut gen = || {
+        let s = String::from("held");
+        yield 1;
+        s.len()
+    };
+    let _ = Pin::new(&mut gen).resume();
+    let _ = Pin::new(&mut gen).resume();
+}
@@ -0,0 +1,17 @@
+// `Self::LEN` appears in the generic default body of `len_of`.  Wherever it's serialized without
+// concrete substs, it should be marked `"unresolved": true` rather than crashing the export.
+use std::mem::size_of;
+
+pub trait Zeroes: Sized {
+    const LEN: usize = 4 * size_of::<Self>();
+
+    fn len_of(&self) -> usize {
+        Self::LEN
+    }
+}
+
+impl Zeroes for u8 {}
+
+fn main() {
+    assert!(1u8.len_of() == 4);
+}
@@ -0,0 +1,10 @@
+// After exporting this crate, `mir-json-link gzip_link.mir -o a.json` and
+// `mir-json-link gzip_link.mir -o b.json.gz` should produce the same JSON: `gunzip -c b.json.gz`
+// should be byte-for-byte identical to `a.json`.
+fn f(x: u32) -> u32 {
+    x * 3
+}
+
+fn main() {
+    f(1);
+}
@@ -0,0 +1,13 @@
+fn cmp<'a, 'b>(x: &'a u32, y: &'b u32) -> bool { *x == *y }
+
+fn call(f: for<'a, 'b> fn(&'a u32, &'b u32) -> bool) -> bool {
+    f(&1, &2)
+}
+
+fn main() {
+    call(cmp);
+}
+
+fn call_closure<F: for<'a> Fn(&'a u8) -> u8>(f: F) -> u8 {
+    f(&1)
+}
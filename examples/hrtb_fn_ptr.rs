@@ -0,0 +1,15 @@
+// In the ADT entry for `S`, the `FnPtr` signature of `hr` should have one `BrNamed` entry (`'a`)
+// in `bound_vars`, and the signature of `st` should have empty `bound_vars`.
+struct S {
+    hr: for<'a> fn(&'a u8) -> &'a u8,
+    st: fn(&'static u8) -> &'static u8,
+}
+
+fn id(x: &u8) -> &u8 {
+    x
+}
+
+fn f(x: &'static u8) -> u8 {
+    let s = S { hr: id, st: id };
+    *(s.hr)(x) + *(s.st)(x)
+}
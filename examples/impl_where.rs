@@ -0,0 +1,14 @@
+trait Foo {
+    fn foo(&self) -> Self;
+    fn bar<U: Default>(&self) -> U;
+}
+
+impl<T> Foo for T where T: Clone {
+    fn foo(&self) -> T { self.clone() }
+    fn bar<U: Default>(&self) -> U { U::default() }
+}
+
+fn main() {
+    1_u8.foo();
+    let _: u16 = 1_u8.bar();
+}
@@ -0,0 +1,21 @@
+// `impls` should contain two entries for `Counter`: an inherent impl with `trait: null`, and an
+// impl of `Clone` with its `trait_ref`.
+pub struct Counter(u32);
+
+impl Counter {
+    pub fn bump(&mut self) {
+        self.0 += 1;
+    }
+}
+
+impl Clone for Counter {
+    fn clone(&self) -> Counter {
+        Counter(self.0)
+    }
+}
+
+fn main() {
+    let mut c = Counter(0);
+    c.bump();
+    assert!(c.clone().0 == 1);
+}
@@ -0,0 +1,14 @@
+// Every statement and terminator should have a `scope`, and the body's `source_scopes` table
+// should describe each one.  With `-Z mir-opt-level=2`, `helper` is inlined into `f`, and `f`'s
+// table should include scopes for the inlined body whose `parent` chain leads to the call site.
+// Our `rustc` doesn't record the inlined instance, so none is exported.
+#[inline(always)]
+fn helper(x: u32) -> u32 {
+    let y = x + 1;
+    y * 2
+}
+
+fn f(x: u32) -> u32 {
+    let z = helper(x);
+    z
+}
@@ -0,0 +1,6 @@
+// Each integer constant should have an `int_val` holding its exact decimal value and a `size` in
+// bytes: "-128" (1), "-170141183460469231731687303715884105728" (16),
+// "340282366920938463463374607431768211455" (16), and "18446744073709551615" (8).
+fn f() -> (i8, i128, u128, u64) {
+    (std::i8::MIN, std::i128::MIN, std::u128::MAX, std::u64::MAX)
+}
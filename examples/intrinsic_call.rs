@@ -0,0 +1,8 @@
+// `std::mem::size_of` is an ordinary function, so its call site has `"intrinsic": null`.  Its body
+// (exported with `core`) calls the `size_of` intrinsic, flagged with `"intrinsic": "size_of"`, as
+// is the direct `transmute` call here.
+fn main() {
+    let n = std::mem::size_of::<u32>();
+    let bits: u32 = unsafe { std::mem::transmute(1.0f32) };
+    assert!(n == 4 && bits == 0x3f80_0000);
+}
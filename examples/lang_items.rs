@@ -0,0 +1,6 @@
+// The `lang_items` crate info section should map `sized` to `core/<hash>::marker[0]::Sized[0]`
+// and `copy` to `core/<hash>::marker[0]::Copy[0]`, along with entries for `box_free`, `eq`,
+// `panic`, and the other lang items defined by this crate's dependencies.
+pub fn f(x: Option<u32>) -> u32 {
+    x.unwrap()
+}
@@ -0,0 +1,6 @@
+// Each local's `layout` gives its size, alignment, and ABI class.  In `f`, `a` should be
+// `Scalar` (`Int(I64, false)`), `b` a `ScalarPair` of two `Int(I8, false)`, and `c` an
+// `Aggregate` with `"sized": true`.
+pub fn f(a: u64, b: (u8, u8), c: [u8; 16]) -> u64 {
+    a + b.0 as u64 + b.1 as u64 + c[0] as u64
+}
@@ -0,0 +1,19 @@
+// `mir-json --list-tests list_tests.rs` should print an array with entries for `first` and
+// `second`, but not `helper`, and exit without writing `list_tests.mir`.
+#![feature(custom_attribute)]
+
+fn helper() -> u32 {
+    2
+}
+
+#[crux_test]
+fn first() {
+    assert!(helper() == 2);
+}
+
+#[crux_test]
+fn second() {
+    assert!(helper() + 1 == 3);
+}
+
+fn main() {}
@@ -0,0 +1,15 @@
+// The local for `count` should have `"debug_name": "count"` and `"mutable": true`.  The local for
+// `step` is an immutable user binding (`"mutable": false`, `"internal": false`), while the
+// temporaries that hold `count < n` are mutable and internal (`"internal": true`).
+fn count_to(n: u32) -> u32 {
+    let step = 1;
+    let mut count = 0;
+    while count < n {
+        count += step;
+    }
+    count
+}
+
+fn main() {
+    assert!(count_to(3) == 3);
+}
@@ -0,0 +1,15 @@
+// The loop header block should list the end of the loop body among its `predecessors` (the back
+// edge), and `start_block` should be `bb0`.
+fn sum(n: u32) -> u32 {
+    let mut i = 0;
+    let mut acc = 0;
+    while i < n {
+        acc += i;
+        i += 1;
+    }
+    acc
+}
+
+fn main() {
+    assert!(sum(4) == 6);
+}
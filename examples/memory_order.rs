@@ -0,0 +1,8 @@
+// `rustc` places the `u64` first to avoid padding, so `S`'s variant should have a `memory_order`
+// of `[1, 0, 2]` and `field_offsets` of `[8, 0, 9]`.
+pub struct S(u8, u64, u8);
+
+fn main() {
+    let s = S(1, 2, 3);
+    assert!(s.0 + s.2 == 4 && s.1 == 2);
+}
@@ -0,0 +1,9 @@
+// In `f`'s call to `take`, the `String` argument should be a `Move` operand with a `ty` of
+// `String`, and the `i32` argument a `Copy` operand with a `ty` of `i32`.
+fn take(s: String, n: i32) -> usize {
+    s.len() + n as usize
+}
+
+fn f(s: String, n: i32) -> usize {
+    take(s, n)
+}
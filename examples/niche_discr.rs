@@ -0,0 +1,15 @@
+// Matching on `x` reads its discriminant.  The `tag` should be a `Niche` encoding, with
+// `dataful_variant` 1 (`Some`) and `niche_start` 0.
+use std::num::NonZeroU32;
+
+fn get(x: Option<NonZeroU32>) -> u32 {
+    match x {
+        Some(n) => n.get(),
+        None => 0,
+    }
+}
+
+fn main() {
+    assert!(get(NonZeroU32::new(5)) == 5);
+    assert!(get(None) == 0);
+}
@@ -0,0 +1,7 @@
+// Building this as a test with `mir-json-rustc-wrapper --test --emit=metadata no_exe_output.rs`
+// produces no executable, so no MIR is exported.  The wrapper should print an error naming the
+// crate `no_exe_output` and exit with status 1, instead of panicking.
+#[test]
+fn t() {
+    assert_eq!(1 + 1, 2);
+}
@@ -0,0 +1,10 @@
+#![no_std]
+
+pub fn add(x: u32, y: u32) -> Option<u32> {
+    x.checked_add(y)
+}
+
+#[cfg_attr(crux, crux_test)]
+fn test_add() {
+    assert!(add(1, 2) == Some(3));
+}
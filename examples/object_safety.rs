@@ -0,0 +1,27 @@
+// `Shape` is object safe, and its `vtable_entries` should list `area` but not `scaled`, which
+// requires `Self: Sized`.  `Cloner` isn't object safe (it returns `Self`), so its entry should
+// have `"object_safe": false` and no `vtable_entries`.
+trait Shape {
+    fn area(&self) -> u32;
+    fn scaled(&self, k: u32) -> u32 where Self: Sized {
+        self.area() * k
+    }
+}
+
+trait Cloner {
+    fn dup(&self) -> Self;
+}
+
+struct Sq(u32);
+
+impl Shape for Sq {
+    fn area(&self) -> u32 { self.0 * self.0 }
+}
+
+fn f(s: &dyn Shape) -> u32 {
+    s.area()
+}
+
+fn main() {
+    f(&Sq(2));
+}
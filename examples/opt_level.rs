@@ -0,0 +1,16 @@
+// Compare the number of `blocks` in `f` with `MIR_JSON_OPT_LEVEL=0` and `MIR_JSON_OPT_LEVEL=2`.
+// Level 0 keeps both branches on the constant `DEBUG`.  Level 2 enables constant propagation, so
+// it can remove them and leave fewer blocks.  `mir_opt_level` in the crate info should match the
+// setting.
+const DEBUG: bool = false;
+
+fn f(x: u32) -> u32 {
+    let mut y = x;
+    if DEBUG {
+        y += 1;
+    }
+    if DEBUG {
+        y *= 2;
+    }
+    y
+}
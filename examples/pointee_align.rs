@@ -0,0 +1,9 @@
+// The `Ref` type of `x` should have `"pointee_align": 8` (on x86_64), and the `RawPtr` type of
+// `p` `"pointee_align": 1`.  `&[u32]` points to 4-byte-aligned elements, and `&dyn Debug` has a
+// `null` pointee alignment, since it comes from the vtable.
+use std::fmt::Debug;
+
+pub fn f(x: &u64, p: *const [u8; 3], s: &[u32], d: &dyn Debug) -> u64 {
+    let _ = (p, s, d);
+    *x
+}
@@ -0,0 +1,9 @@
+// With `MIR_JSON_PRETTY_TYPES=1`, the return type of `bytes` should have
+// `"pretty": "std::vec::Vec<u8>"`.
+fn bytes() -> Vec<u8> {
+    vec![1, 2, 3]
+}
+
+fn main() {
+    assert!(bytes().len() == 3);
+}
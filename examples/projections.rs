@@ -0,0 +1,7 @@
+fn first<I: Iterator>(mut i: I) -> Option<I::Item> {
+    i.next()
+}
+
+fn main() {
+    first(vec![1_u8, 2].into_iter());
+}
@@ -7,3 +7,9 @@ fn f() {
     let y = &S;
     let z = &C2;
 }
+
+// The slice literal is promoted to a constant.  `g`'s `promoted` list should name its body, and
+// the `Promoted` place in `g`'s body should have index 0.
+fn g() -> &'static [u32] {
+    &[1, 2, 3]
+}
@@ -0,0 +1,28 @@
+// When linked with `MIR_JSON_PRUNE` (or `mir-json-link --prune`), `unused_helper` should not
+// appear in the output, while `Doubler::apply` should be kept, since it's reachable from the
+// `#[crux_test]` root through the `dyn Op` vtable.  Without pruning, both are kept.
+#![feature(custom_attribute)]
+
+trait Op {
+    fn apply(&self, x: u32) -> u32;
+}
+
+struct Doubler;
+
+impl Op for Doubler {
+    fn apply(&self, x: u32) -> u32 {
+        x * 2
+    }
+}
+
+pub fn unused_helper() -> u32 {
+    7
+}
+
+#[crux_test]
+fn test_op() {
+    let op: &dyn Op = &Doubler;
+    assert!(op.apply(2) == 4);
+}
+
+fn main() {}
@@ -0,0 +1,14 @@
+// `referenced_types` should list both `Vec` and `Foo`, plus the ADTs used in `Vec`'s fields
+// (`RawVec`, `Unique`, ...).
+pub struct Foo {
+    x: u32,
+}
+
+pub fn make() -> Vec<Foo> {
+    vec![Foo { x: 1 }, Foo { x: 2 }]
+}
+
+fn main() {
+    let v = make();
+    assert!(v[1].x == 2);
+}
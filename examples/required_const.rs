@@ -0,0 +1,10 @@
+#![feature(rustc_attrs)]
+
+#[rustc_args_required_const(1)]
+fn shift(x: u32, amt: u32) -> u32 {
+    x << amt
+}
+
+fn main() {
+    shift(1, 2);
+}
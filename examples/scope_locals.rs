@@ -0,0 +1,11 @@
+// In `f`'s `source_scopes`, the outer `x` should be listed in the `locals` of one scope, and the
+// shadowing `x` from the inner block in the `locals` of a scope nested inside it.  `y` should be
+// in a scope nested inside the outer `x`'s.
+pub fn f(a: u32) -> u32 {
+    let x = a + 1;
+    let y = {
+        let x = x * 2;
+        x + 1
+    };
+    x + y
+}
@@ -0,0 +1,14 @@
+// With `MIR_JSON_SPLIT=out`, this writes `out/split/` instead of `split.mir`.  The `fns` table
+// of `out/split/index.json` should have an entry for each of `main`, `f`, and `g`, and each entry
+// should name a file in that directory containing the function's body.
+fn f(x: u32) -> u32 {
+    x + 1
+}
+
+fn g(x: u32) -> u32 {
+    f(x) * 2
+}
+
+fn main() {
+    g(1);
+}
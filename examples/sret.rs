@@ -0,0 +1,16 @@
+#[repr(C)]
+pub struct Big {
+    a: u64,
+    b: u64,
+    c: u64,
+    d: u64,
+}
+
+pub extern "C" fn make_big(x: u64) -> Big {
+    Big { a: x, b: x, c: x, d: x }
+}
+
+fn main() {
+    let big = make_big(1);
+    assert!(big.a == big.d);
+}
@@ -0,0 +1,12 @@
+#![feature(thread_local)]
+
+// `COUNTER` should be emitted with `"mutable": true` and `"thread_local": false`, and `PER_THREAD`
+// with `"mutable": false` and `"thread_local": true`.
+static mut COUNTER: u32 = 0;
+
+#[thread_local]
+static PER_THREAD: u32 = 7;
+
+fn f() -> u32 {
+    unsafe { COUNTER += 1; COUNTER + PER_THREAD }
+}
@@ -0,0 +1,10 @@
+// The loop body of `f` should begin with a `StorageLive` statement whose `slvar` is `y`'s local,
+// and end with a matching `StorageDead` (with `sdvar`) before jumping back to the loop header.
+pub fn f(n: u32) -> u32 {
+    let mut acc = 0;
+    for i in 0..n {
+        let y = i * 2;
+        acc += y;
+    }
+    acc
+}
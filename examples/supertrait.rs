@@ -0,0 +1,25 @@
+// `Named`'s `super_predicates` should include a trait predicate for `Base<u8>`.
+trait Base<T> {
+    fn base(&self) -> T;
+}
+
+trait Named: Base<u8> {
+    fn name(&self) -> u8 {
+        self.base() + 1
+    }
+}
+
+struct S;
+
+impl Base<u8> for S {
+    fn base(&self) -> u8 {
+        1
+    }
+}
+
+impl Named for S {}
+
+fn main() {
+    let n: &dyn Named = &S;
+    assert!(n.name() == 2);
+}
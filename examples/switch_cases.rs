@@ -0,0 +1,11 @@
+// The `SwitchInt` on `x` should have `cases` for the values `-1`, `0`, `1`, and `100`, each paired
+// with the block for its arm, and `otherwise` should be the block for the wildcard arm.
+fn f(x: i32) -> u8 {
+    match x {
+        -1 => 10,
+        0 => 20,
+        1 => 30,
+        100 => 40,
+        _ => 50,
+    }
+}
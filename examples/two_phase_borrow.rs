@@ -0,0 +1,12 @@
+// In `f`, the autoref of `v` for the `push` call should be a `Ref` rvalue with `"borrowkind":
+// "Mut"`, `"two_phase": true`, and a `refvar_ty` of `Vec<usize>`.  The explicit `&mut x` in `g`
+// is an ordinary mutable borrow, with `"two_phase": false`.
+pub fn f(v: &mut Vec<usize>) {
+    v.push(v.len());
+}
+
+pub fn g(mut x: u32) -> u32 {
+    let r = &mut x;
+    *r += 1;
+    x
+}
@@ -0,0 +1,27 @@
+// The ADT definitions of `Void` and `HasNever` should have `"uninhabited": true`, and `Inhabited`
+// should have `"uninhabited": false`.  `x`'s local in `absurd` should also have a layout with
+// `"uninhabited": true`, and the `!` type should be marked uninhabited wherever it appears.
+#![feature(never_type)]
+
+pub enum Void {}
+
+pub struct HasNever {
+    pub x: u32,
+    pub never: !,
+}
+
+pub struct Inhabited {
+    pub x: u32,
+}
+
+pub fn absurd(x: Void) -> u32 {
+    match x {}
+}
+
+pub fn absurd2(x: HasNever) -> u32 {
+    x.never
+}
+
+pub fn inhabited(x: Inhabited) -> u32 {
+    x.x
+}
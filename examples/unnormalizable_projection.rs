@@ -0,0 +1,14 @@
+// `u8: Foo` doesn't hold, but `trivial_bounds` lets `f` assume it anyway, so the type of `x`
+// contains the projection `<u8 as Foo>::A`, which can't be normalized.  `f`'s `projections`
+// should leave it out rather than crash the exporter.
+#![feature(trivial_bounds)]
+#![allow(trivial_bounds)]
+
+pub trait Foo {
+    type A;
+}
+
+pub fn f() -> u32 where u8: Foo {
+    let x: Option<<u8 as Foo>::A> = None;
+    if x.is_some() { 1 } else { 0 }
+}
@@ -0,0 +1,21 @@
+// `never` matches on an uninhabited type, so its body ends in an `Unreachable` terminator.
+// `no_unwind` can't unwind, so the cleanup path for its call to `may_panic` ends in an `Abort`
+// terminator instead of `Resume`.  Neither `unreachable!()` (a panic) nor `intrinsics::abort()`
+// (a call to an intrinsic) produces these terminators.
+#![feature(unwind_attributes)]
+
+enum Void {}
+
+fn never(v: Void) -> u32 {
+    match v {}
+}
+
+fn may_panic(x: u32) -> u32 {
+    if x == 0 { panic!("zero") }
+    x
+}
+
+#[unwind(aborts)]
+pub extern "C" fn no_unwind(x: u32) -> u32 {
+    may_panic(x)
+}
@@ -0,0 +1,10 @@
+// The cast from `&[u8; 4]` to `&[u8]` should be a `Pointer` cast of kind `Unsize`, with
+// `unsized_ty` `[u8]`.
+fn total(xs: &[u8]) -> u32 {
+    xs.iter().map(|&x| x as u32).sum()
+}
+
+fn main() {
+    let arr = [1, 2, 3, 4];
+    assert!(total(&arr) == 10);
+}
@@ -0,0 +1,8 @@
+// The ADT entry for `NonZeroU8` should have `"valid_range": ["1", "255"]`.  `bool` and `char`
+// types should have `["0", "1"]` and `["0", "1114111"]`.  The `Niche` tag of `Option<NonZeroU8>`
+// also has a `valid_range`, which is the range of the niche field.
+use std::num::NonZeroU8;
+
+fn f(x: NonZeroU8, b: bool, c: char) -> Option<NonZeroU8> {
+    if b && c != 'a' { Some(x) } else { None }
+}
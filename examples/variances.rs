@@ -0,0 +1,10 @@
+// The ADT entry for `MyCell` should have `"variances": ["Invariant"]`, since `T` appears inside an
+// `UnsafeCell`.  `Covariant` should have `["Covariant"]`, and `Contra` should have
+// `["Contravariant"]`.
+use std::cell::UnsafeCell;
+
+struct MyCell<T>(UnsafeCell<T>);
+struct Covariant<T>(fn() -> T);
+struct Contra<T>(fn(T));
+
+fn f(a: MyCell<u8>, b: Covariant<u8>, c: Contra<u8>) {}
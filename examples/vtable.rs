@@ -0,0 +1,16 @@
+trait Shape {
+    fn area(&self) -> u32;
+    fn sides(&self) -> u32;
+}
+
+struct Square(u32);
+
+impl Shape for Square {
+    fn area(&self) -> u32 { self.0 * self.0 }
+    fn sides(&self) -> u32 { 4 }
+}
+
+fn main() {
+    let s: Box<dyn Shape> = Box::new(Square(3));
+    assert!(s.area() == 9 && s.sides() == 4);
+}
@@ -0,0 +1,269 @@
+use rustc::ty::{self, TyCtxt, TypeFoldable};
+use rustc::ty::layout::{
+    FnTypeExt, HasDataLayout, HasParamEnv, HasTyCtxt, LayoutOf, TargetDataLayout, TyLayout,
+    Abi, DiscriminantKind, Scalar, VariantIdx, Variants,
+};
+use rustc_target::abi::{AbiAndPrefAlign, Endian};
+use rustc_target::abi::call::{ArgType, FnType, PassMode};
+use rustc_target::spec::{HasTargetSpec, Target};
+use serde_json;
+
+use analyz::to_json::*;
+
+/// Layout context for computing `FnType`s outside of codegen.  `FnType::of_instance` wants a
+/// context whose `layout_of` is infallible, like the one codegen uses, so we panic on layout
+/// errors here instead of returning them.  Callers should check with the fallible
+/// `tcx.layout_of` first, as `fn_abi_json` does.
+pub struct AbiCx<'tcx> {
+    pub tcx: TyCtxt<'tcx>,
+}
+
+impl HasDataLayout for AbiCx<'_> {
+    fn data_layout(&self) -> &TargetDataLayout {
+        &self.tcx.data_layout
+    }
+}
+
+impl HasTargetSpec for AbiCx<'_> {
+    fn target_spec(&self) -> &Target {
+        &self.tcx.sess.target.target
+    }
+}
+
+impl<'tcx> HasTyCtxt<'tcx> for AbiCx<'tcx> {
+    fn tcx(&self) -> TyCtxt<'tcx> {
+        self.tcx
+    }
+}
+
+impl<'tcx> HasParamEnv<'tcx> for AbiCx<'tcx> {
+    fn param_env(&self) -> ty::ParamEnv<'tcx> {
+        ty::ParamEnv::reveal_all()
+    }
+}
+
+impl<'tcx> LayoutOf for AbiCx<'tcx> {
+    type Ty = ty::Ty<'tcx>;
+    type TyLayout = TyLayout<'tcx>;
+
+    fn layout_of(&self, ty: ty::Ty<'tcx>) -> TyLayout<'tcx> {
+        self.tcx.layout_of(ty::ParamEnv::reveal_all().and(ty))
+            .unwrap_or_else(|e| panic!("failed to compute layout of {:?}: {}", ty, e))
+    }
+}
+
+impl ToJson<'_> for PassMode {
+    fn to_json(&self, _mir: &mut MirState) -> serde_json::Value {
+        match self {
+            &PassMode::Ignore(_) => json!({"kind": "Ignore"}),
+            &PassMode::Direct(_) => json!({"kind": "Direct"}),
+            &PassMode::Pair(_, _) => json!({"kind": "Pair"}),
+            &PassMode::Cast(ref target) => json!({
+                "kind": "Cast",
+                "target": format!("{:?}", target),
+            }),
+            &PassMode::Indirect(_, ref extra) => json!({
+                "kind": "Indirect",
+                "unsized": extra.is_some(),
+            }),
+        }
+    }
+}
+
+impl<'tcx> ToJson<'tcx> for ArgType<'tcx, ty::Ty<'tcx>> {
+    fn to_json(&self, mir: &mut MirState<'_, 'tcx>) -> serde_json::Value {
+        json!({
+            "ty": self.layout.ty.to_json(mir),
+            "mode": self.mode.to_json(mir),
+        })
+    }
+}
+
+/// Compute the calling convention rustc uses for `inst`: how each argument is passed, and whether
+/// the return value comes back directly or through an implicit out-pointer (`Indirect`, which is
+/// how large `#[repr(C)]` structs are returned under the C ABI).  Returns `None` if the layout of
+/// an argument or the return type can't be computed (for example, because it is too big for the
+/// target), since `FnType::of_instance` would panic.
+pub fn fn_abi_json<'tcx>(
+    mir: &mut MirState<'_, 'tcx>,
+    inst: ty::Instance<'tcx>,
+) -> Option<serde_json::Value> {
+    let tcx = mir.state.tcx;
+    let sig = tcx.normalize_erasing_late_bound_regions(
+        ty::ParamEnv::reveal_all(),
+        &inst.fn_sig(tcx),
+    );
+    for &ty in sig.inputs().iter().chain(Some(&sig.output())) {
+        if let Err(e) = tcx.layout_of(ty::ParamEnv::reveal_all().and(ty)) {
+            eprintln!("warning: omitting fn_abi for {:?}: failed to compute layout of {:?}: {}",
+                inst, ty, e);
+            return None;
+        }
+    }
+
+    let cx = AbiCx { tcx };
+    let fn_ty: FnType<'tcx, ty::Ty<'tcx>> = FnType::of_instance(&cx, &inst);
+    Some(json!({
+        "args": fn_ty.args.iter().map(|a| a.to_json(mir)).collect::<Vec<_>>(),
+        "ret": fn_ty.ret.to_json(mir),
+        "c_variadic": fn_ty.c_variadic,
+        "conv": format!("{:?}", fn_ty.conv),
+    }))
+}
+
+/// Describe the physical placement of the fields of variant `idx` of `ty`.  `memory_order` lists
+/// field indices by increasing offset (`rustc` may reorder fields to reduce padding), and
+/// `field_offsets` gives the byte offset of each field in declaration order.  Returns `None` if
+/// the layout of `ty` can't be computed, which is always the case for generic ADT definitions.
+pub fn variant_layout_json<'tcx>(
+    tcx: TyCtxt<'tcx>,
+    ty: ty::Ty<'tcx>,
+    idx: VariantIdx,
+) -> Option<serde_json::Value> {
+    if ty.needs_subst() {
+        return None;
+    }
+    let layout = tcx.layout_of(ty::ParamEnv::reveal_all().and(ty)).ok()?;
+    let cx = AbiCx { tcx };
+    let layout = layout.for_variant(&cx, idx);
+    let fields = &layout.fields;
+    Some(json!({
+        "memory_order": fields.index_by_increasing_offset().collect::<Vec<_>>(),
+        "field_offsets": (0 .. fields.count()).map(|i| fields.offset(i).bytes())
+            .collect::<Vec<_>>(),
+    }))
+}
+
+/// Describe how the discriminant of `ty` is encoded in memory.  `Tag` stores the discriminant
+/// directly in the field `tag_field` of the layout, while `Niche` stores it in invalid values of
+/// the field of the `dataful_variant`, like how `None` is represented by 0 in
+/// `Option<NonZeroU32>`.  Returns `None` if the layout of `ty` can't be computed.
+pub fn tag_layout_json<'tcx>(tcx: TyCtxt<'tcx>, ty: ty::Ty<'tcx>) -> Option<serde_json::Value> {
+    if ty.needs_subst() {
+        return None;
+    }
+    let layout = tcx.layout_of(ty::ParamEnv::reveal_all().and(ty)).ok()?;
+    Some(match layout.variants {
+        Variants::Single { index } => json!({
+            "kind": "Single",
+            "variant": index.as_usize(),
+        }),
+        Variants::Multiple { ref discr, ref discr_kind, discr_index, .. } => {
+            let mut j = json!({
+                "tag_ty": format!("{:?}", discr.value),
+                "tag_field": discr_index,
+                "valid_range": scalar_valid_range(discr),
+            });
+            match *discr_kind {
+                DiscriminantKind::Tag => {
+                    j["kind"] = "Tag".into();
+                },
+                DiscriminantKind::Niche { dataful_variant, ref niche_variants, niche_start } => {
+                    j["kind"] = "Niche".into();
+                    j["dataful_variant"] = dataful_variant.as_usize().into();
+                    j["niche_variants"] = json!([
+                        niche_variants.start().as_usize(),
+                        niche_variants.end().as_usize(),
+                    ]);
+                    j["niche_start"] = niche_start.to_string().into();
+                },
+            }
+            j
+        },
+    })
+}
+
+fn scalar_valid_range(s: &Scalar) -> serde_json::Value {
+    json!([s.valid_range.start().to_string(), s.valid_range.end().to_string()])
+}
+
+/// Get the range of valid bit patterns for `ty`, if it's represented as a single scalar, like
+/// `bool` (0 to 1), `char` (0 to 0x10FFFF), or `NonZeroU8` (1 to 255).  The range is inclusive,
+/// and wraps around if `start > end`.  The bounds are strings, since they may not fit in a JSON
+/// number.  Returns `None` for non-scalar types and types whose layout can't be computed.
+pub fn valid_range_json<'tcx>(tcx: TyCtxt<'tcx>, ty: ty::Ty<'tcx>) -> Option<serde_json::Value> {
+    if ty.needs_subst() {
+        return None;
+    }
+    let layout = tcx.layout_of(ty::ParamEnv::reveal_all().and(ty)).ok()?;
+    match layout.abi {
+        Abi::Scalar(ref s) => Some(scalar_valid_range(s)),
+        _ => None,
+    }
+}
+
+/// Check whether `ty` has no values, like `enum Void {}` or a struct with a `!` field.  Code that
+/// produces a value of such a type is unreachable.  Returns `None` if the layout of `ty` can't be
+/// computed.
+pub fn uninhabited_json<'tcx>(tcx: TyCtxt<'tcx>, ty: ty::Ty<'tcx>) -> Option<bool> {
+    if ty.needs_subst() {
+        return None;
+    }
+    let layout = tcx.layout_of(ty::ParamEnv::reveal_all().and(ty)).ok()?;
+    Some(layout.abi.is_uninhabited())
+}
+
+/// Get the alignment in bytes that a pointer to `ty` must have to be dereferenced.  For slices and
+/// `str`, this is the alignment of the elements.  Returns `None` for trait objects, whose alignment
+/// is only known from the vtable, and for types whose layout can't be computed.
+pub fn pointee_align_json<'tcx>(tcx: TyCtxt<'tcx>, ty: ty::Ty<'tcx>) -> Option<u64> {
+    if ty.needs_subst() {
+        return None;
+    }
+    if let ty::TyKind::Dynamic(..) = ty.sty {
+        return None;
+    }
+    let layout = tcx.layout_of(ty::ParamEnv::reveal_all().and(ty)).ok()?;
+    Some(layout.align.abi.bytes())
+}
+
+/// Describe how values of `layout` are represented when passed around, as `rustc` classifies them:
+/// a single `Scalar`, a `ScalarPair` (like `(u8, u8)` or a fat pointer), a SIMD `Vector`, or an
+/// `Aggregate` that lives in memory.  `Uninhabited` types have no values at all.
+pub fn layout_abi_json(layout: &TyLayout) -> serde_json::Value {
+    match layout.abi {
+        Abi::Uninhabited => json!({"kind": "Uninhabited"}),
+        Abi::Scalar(ref s) => json!({
+            "kind": "Scalar",
+            "value": format!("{:?}", s.value),
+        }),
+        Abi::ScalarPair(ref a, ref b) => json!({
+            "kind": "ScalarPair",
+            "values": [format!("{:?}", a.value), format!("{:?}", b.value)],
+        }),
+        Abi::Vector { ref element, count } => json!({
+            "kind": "Vector",
+            "element": format!("{:?}", element.value),
+            "count": count,
+        }),
+        Abi::Aggregate { sized } => json!({"kind": "Aggregate", "sized": sized}),
+    }
+}
+
+/// Describe the target's data layout: pointer size and alignment, endianness, and the alignment of
+/// each primitive integer and float type.  Sizes and alignments are in bytes.
+pub fn data_layout_json(tcx: TyCtxt) -> serde_json::Value {
+    let dl = &tcx.data_layout;
+    let align = |a: AbiAndPrefAlign| json!({"abi": a.abi.bytes(), "pref": a.pref.bytes()});
+    json!({
+        "endian": match dl.endian {
+            Endian::Little => "little",
+            Endian::Big => "big",
+        },
+        "pointer_size": dl.pointer_size.bytes(),
+        "pointer_align": align(dl.pointer_align),
+        "aggregate_align": align(dl.aggregate_align),
+        "int_align": {
+            "i1": align(dl.i1_align),
+            "i8": align(dl.i8_align),
+            "i16": align(dl.i16_align),
+            "i32": align(dl.i32_align),
+            "i64": align(dl.i64_align),
+            "i128": align(dl.i128_align),
+        },
+        "float_align": {
+            "f32": align(dl.f32_align),
+            "f64": align(dl.f64_align),
+        },
+    })
+}
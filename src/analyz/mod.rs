@@ -1,12 +1,14 @@
 #![macro_use]
 
-use rustc::ty::{self, TyCtxt, List, TyS};
+use rustc::ty::{self, TyCtxt, List, TyS, ToPredicate, TypeFoldable};
 use rustc::mir::{self, Body};
 use rustc::hir;
+use rustc::hir::CodegenFnAttrFlags;
 use rustc::hir::def::DefKind;
 use rustc::hir::def_id::{self, DefId, LOCAL_CRATE};
+use rustc::middle::lang_items;
 use rustc::mir::mono::MonoItem;
-use rustc::session::config::OutputType;
+use rustc::session::config::{EntryFnType, OutputType};
 use rustc::traits;
 use rustc::ty::subst::Subst;
 use rustc_codegen_utils;
@@ -14,8 +16,14 @@ use rustc_driver::source_name;
 use rustc_interface::interface::Compiler;
 use rustc_mir::monomorphize::collector::{self, MonoItemCollectionMode};
 use rustc_target::spec::abi;
+use syntax::ast;
+use syntax::attr;
+use syntax::print::pprust;
 use syntax::symbol::Symbol;
 use std::collections::{HashMap, HashSet};
+use std::env;
+use std::error::Error;
+use std::fmt;
 use std::fmt::Write as FmtWrite;
 use std::io;
 use std::io::Write;
@@ -26,11 +34,14 @@ use serde_json;
 use serde_cbor;
 
 #[macro_use]
-mod to_json;
+pub mod to_json;
 mod ty_json;
+mod abi_json;
 use analyz::to_json::*;
 use analyz::ty_json::*;
+use analyz::abi_json::*;
 use lib_util::{self, JsonOutput, EntryKind};
+use validate;
 
 basic_json_enum_impl!(mir::BinOp);
 
@@ -61,16 +72,35 @@ impl<'tcx> ToJson<'tcx> for mir::AggregateKind<'tcx> {
             &mir::AggregateKind::Adt(_, _, _, _, _) => {
                 panic!("adt should be handled upstream")
             }
+            // For closures and generators, the aggregate's operands are the captured upvars, in
+            // the same order as `upvar_tys`.
             &mir::AggregateKind::Closure(ref defid, ref closuresubsts) => {
+                let tcx = mir.state.tcx;
+                let upvar_tys = closuresubsts.upvar_tys(*defid, tcx)
+                    .map(|ty| ty.to_json(mir))
+                    .collect::<Vec<_>>();
                 json!({
                     "kind": "Closure",
                     "defid": defid.to_json(mir),
-                    "closuresubsts": closuresubsts.substs.to_json(mir)
+                    "closuresubsts": closuresubsts.substs.to_json(mir),
+                    "upvar_tys": upvar_tys,
                 })
             }
-            &mir::AggregateKind::Generator(_, _, _,) => {
-                // TODO
-                json!({"kind": "Generator"})
+            &mir::AggregateKind::Generator(ref defid, ref gensubsts, movability) => {
+                let tcx = mir.state.tcx;
+                let upvar_tys = gensubsts.upvar_tys(*defid, tcx)
+                    .map(|ty| ty.to_json(mir))
+                    .collect::<Vec<_>>();
+                let sig = gensubsts.sig(*defid, tcx);
+                json!({
+                    "kind": "Generator",
+                    "defid": defid.to_json(mir),
+                    "gensubsts": gensubsts.substs.to_json(mir),
+                    "upvar_tys": upvar_tys,
+                    "yield_ty": sig.yield_ty.to_json(mir),
+                    "return_ty": sig.return_ty.to_json(mir),
+                    "movable": movability == hir::GeneratorMovability::Movable,
+                })
             }
         }
     }
@@ -124,6 +154,30 @@ fn vtable_descriptor_for_cast<'tcx>(
     Some(trait_ref)
 }
 
+/// Check whether a cast is an unsizing coercion from one trait object to another, such as `&(dyn
+/// Trait + Send)` to `&dyn Trait`.  These reuse the source's vtable instead of building a new one.
+/// On our current `rustc`, the principal trait must be the same on both sides (trait upcasting and
+/// `dyn*` are not supported), so only auto traits can be dropped.
+fn is_dyn_to_dyn_cast<'tcx>(
+    kind: mir::CastKind,
+    old_ty: ty::Ty<'tcx>,
+    new_ty: ty::Ty<'tcx>,
+) -> bool {
+    if kind != mir::CastKind::Pointer(ty::adjustment::PointerCast::Unsize) {
+        return false;
+    }
+    let pointee = |ty: ty::Ty<'tcx>| match ty.sty {
+        ty::TyKind::Ref(_, ty, _) => Some(ty),
+        ty::TyKind::RawPtr(ref tm) => Some(tm.ty),
+        ty::TyKind::Adt(adt_def, _) if adt_def.is_box() => Some(ty.boxed_ty()),
+        _ => None,
+    };
+    match (pointee(old_ty).map(|t| &t.sty), pointee(new_ty).map(|t| &t.sty)) {
+        (Some(&ty::TyKind::Dynamic(..)), Some(&ty::TyKind::Dynamic(..))) => true,
+        _ => false,
+    }
+}
+
 impl<'tcx> ToJson<'tcx> for mir::Rvalue<'tcx> {
     fn to_json(&self, mir: &mut MirState<'_, 'tcx>) -> serde_json::Value {
         match self {
@@ -140,14 +194,25 @@ impl<'tcx> ToJson<'tcx> for mir::Rvalue<'tcx> {
                     "len": s
                 })
             }
-            &mir::Rvalue::Ref(_, ref bk, ref l) => {
+            &mir::Rvalue::Ref(region, ref bk, ref l) => {
+                let body = mir.mir.unwrap();
+                let place_ty = l.ty(body, mir.state.tcx).ty;
                 json!({
                     "kind": "Ref",
-                    "region": "unimplement",
+                    // Regions are erased in optimized MIR, so this is almost always `Erased`.
+                    "region": region.to_json(mir),
                     "borrowkind": bk.to_json(mir),
-                    "refvar": l.to_json(mir)
+                    // A two-phase borrow (like the `&mut v` in `v.push(v.len())`) is only
+                    // "reserved" until its first use, and shared borrows of the place are
+                    // allowed in the meantime.
+                    "two_phase": match *bk {
+                        mir::BorrowKind::Mut { allow_two_phase_borrow } => allow_two_phase_borrow,
+                        _ => false,
+                    },
+                    "refvar": l.to_json(mir),
+                    "refvar_ty": place_ty.to_json(mir),
                 })
-            } // UNUSED
+            }
             &mir::Rvalue::Len(ref l) => {
                 json!({"kind": "Len", "lv": l.to_json(mir)})
             }
@@ -166,6 +231,23 @@ impl<'tcx> ToJson<'tcx> for mir::Rvalue<'tcx> {
                         "vtable": vtable_name(mir, vtable_desc),
                     });
                     mir.used.vtables.insert(vtable_desc);
+                } else if is_dyn_to_dyn_cast(*ck, op_ty, ty) {
+                    j["type"] = json!({
+                        "kind": "UnsizeDyn",
+                        "from_ty": op_ty.to_json(mir),
+                        "to_ty": ty.to_json(mir),
+                    });
+                } else if let mir::CastKind::Pointer(ref pc) = *ck {
+                    j["type"]["from_ty"] = op_ty.to_json(mir);
+                    j["type"]["to_ty"] = ty.to_json(mir);
+                    // For unsizing casts, like `&[T; N]` to `&[T]`, also record the unsized
+                    // pointee type, which determines the kind of metadata the result carries.
+                    // This is omitted for `CoerceUnsized` smart pointers, like `Rc`.
+                    if *pc == ty::adjustment::PointerCast::Unsize {
+                        if let Some(tm) = ty.builtin_deref(true) {
+                            j["type"]["unsized_ty"] = tm.ty.to_json(mir);
+                        }
+                    }
                 }
                 j
             }
@@ -178,11 +260,14 @@ impl<'tcx> ToJson<'tcx> for mir::Rvalue<'tcx> {
                 })
             }
             &mir::Rvalue::CheckedBinaryOp(ref binop, ref op1, ref op2) => {
+                // The result is a `(T, bool)` tuple, where the `bool` is set on overflow.
+                let result_ty = self.ty(mir.mir.unwrap(), mir.state.tcx);
                 json!({
                     "kind": "CheckedBinaryOp",
                     "op": binop.to_json(mir),
                     "L": op1.to_json(mir),
-                    "R": op2.to_json(mir)
+                    "R": op2.to_json(mir),
+                    "result_ty": result_ty.to_json(mir),
                 })
             }
             &mir::Rvalue::NullaryOp(ref no, ref t) => {
@@ -200,7 +285,17 @@ impl<'tcx> ToJson<'tcx> for mir::Rvalue<'tcx> {
                 })
             }
             &mir::Rvalue::Discriminant(ref lv) => {
-                json!({"kind": "Discriminant", "val": lv.to_json(mir)})
+                let tcx = mir.state.tcx;
+                let body = mir.mir.unwrap();
+                let enum_ty = lv.ty(body, tcx).ty;
+                json!({
+                    "kind": "Discriminant",
+                    "val": lv.to_json(mir),
+                    // The integer type of the discriminant value produced by this rvalue.  The
+                    // value stored in memory may be encoded differently; see `tag`.
+                    "discr_ty": self.ty(body, tcx).to_json(mir),
+                    "tag": tag_layout_json(tcx, enum_ty),
+                })
             }
             &mir::Rvalue::Aggregate(ref ak, ref opv) => {
                 if ty_json::is_adt_ak(ak) {
@@ -222,10 +317,52 @@ impl<'tcx> ToJson<'tcx> for mir::Rvalue<'tcx> {
 
 impl<'tcx> ToJson<'tcx> for mir::Place<'tcx> {
     fn to_json(&self, mir: &mut MirState<'_, 'tcx>) -> serde_json::Value {
-        json!({
+        let mut j = json!({
             "base": self.base.to_json(mir),
             "data" : self.projection.to_json(mir)
-        })
+        });
+        annotate_projection_tys(mir, self, &mut j["data"]);
+        j
+    }
+}
+
+/// Add a `result_ty` to each projection element in `j` (the serialized `place.projection`), giving
+/// the type of the place after applying that projection.
+fn annotate_projection_tys<'tcx>(
+    ms: &mut MirState<'_, 'tcx>,
+    place: &mir::Place<'tcx>,
+    j: &mut serde_json::Value,
+) {
+    let tcx = ms.state.tcx;
+    let body = match ms.mir {
+        Some(x) => x,
+        None => return,
+    };
+
+    // `place.projection` is a linked list with the outermost projection first.
+    let mut elems = Vec::new();
+    let mut cur = &place.projection;
+    while let Some(ref proj) = *cur {
+        elems.push(&proj.elem);
+        cur = &proj.base;
+    }
+
+    let base_ty = match place.base {
+        mir::PlaceBase::Local(l) => body.local_decls[l].ty,
+        mir::PlaceBase::Static(ref s) => s.ty,
+    };
+    let mut place_ty = mir::tcx::PlaceTy::from_ty(base_ty);
+    let mut tys = Vec::with_capacity(elems.len());
+    for elem in elems.iter().rev() {
+        place_ty = place_ty.projection_ty(tcx, elem);
+        tys.push(place_ty.ty);
+    }
+
+    // Walk the JSON in the same outermost-first order as `elems`.
+    let mut cur = j;
+    for ty in tys.into_iter().rev() {
+        cur["data"]["result_ty"] = ty.to_json(ms);
+        cur = &mut cur["base"];
     }
 }
 
@@ -236,6 +373,8 @@ impl<'tcx> ToJson<'tcx> for mir::PlaceBase<'tcx> {
                 json!({"kind": "Local", "localvar": local_json(ms, *l) })
             }
             &mir::PlaceBase::Static(ref s) => match s.kind {
+                // `index` refers to the enclosing function's `promoted` list, which gives the
+                // name of the `fns` entry (and `statics` entry) for the promoted constant.
                 mir::StaticKind::Promoted(idx) => {
                     json!({
                         "kind": "Promoted",
@@ -310,16 +449,49 @@ impl ToJson<'_> for mir::Field {
     }
 }
 
-basic_json_impl!(mir::AssertMessage<'a>, 'a);
+/// The reason an `Assert` terminator fails.  `text` is the message `rustc` would print, and
+/// `BoundsCheck` and `Overflow` also give the operands and operator involved.
+impl<'tcx> ToJson<'tcx> for mir::AssertMessage<'tcx> {
+    fn to_json(&self, mir: &mut MirState<'_, 'tcx>) -> serde_json::Value {
+        let mut j = match self {
+            &mir::AssertMessage::BoundsCheck { ref len, ref index } => json!({
+                "kind": "BoundsCheck",
+                "len": len.to_json(mir),
+                "index": index.to_json(mir),
+            }),
+            &mir::AssertMessage::Overflow(op) => json!({
+                "kind": "Overflow",
+                "op": op.to_json(mir),
+            }),
+            &mir::AssertMessage::OverflowNeg => json!({"kind": "OverflowNeg"}),
+            &mir::AssertMessage::DivisionByZero => json!({"kind": "DivisionByZero"}),
+            &mir::AssertMessage::RemainderByZero => json!({"kind": "RemainderByZero"}),
+            _ => json!({"kind": "Other"}),
+        };
+        j["text"] = format!("{:?}", self).into();
+        j
+    }
+}
+
+fn place_ty_json<'tcx>(
+    mir: &mut MirState<'_, 'tcx>,
+    place: &mir::Place<'tcx>,
+) -> serde_json::Value {
+    let body = mir.mir.unwrap();
+    place.ty(body, mir.state.tcx).ty.to_json(mir)
+}
 
 impl<'tcx> ToJson<'tcx> for mir::Operand<'tcx> {
     fn to_json(&self, mir: &mut MirState<'_, 'tcx>) -> serde_json::Value {
+        // A `Move` leaves its place uninitialized (unless its type is `Copy`, in which case
+        // `rustc` would have used `Copy`), so the kinds must stay distinct.  Constants carry their
+        // type in `data` already.
         match self {
             &mir::Operand::Copy(ref l) => {
-                json!({"kind": "Copy", "data": l.to_json(mir)})
+                json!({"kind": "Copy", "data": l.to_json(mir), "ty": place_ty_json(mir, l)})
             }
             &mir::Operand::Move(ref l) => {
-                json!({"kind": "Move", "data": l.to_json(mir)})
+                json!({"kind": "Move", "data": l.to_json(mir), "ty": place_ty_json(mir, l)})
             }
             &mir::Operand::Constant(ref l) => {
                 json!({"kind": "Constant", "data": l.to_json(mir)})
@@ -340,13 +512,26 @@ impl<'tcx> ToJson<'tcx> for mir::Constant<'tcx> {
 impl<'tcx> ToJson<'tcx> for mir::LocalDecl<'tcx> {
     fn to_json(&self, mir: &mut MirState<'_, 'tcx>) -> serde_json::Value {
         let pos = mir.state.session.source_map().span_to_string(self.source_info.span);
+        let layout = mir.state.tcx.layout_of(ty::ParamEnv::reveal_all().and(self.ty))
+            .expect("failed to get layout");
         json!({
             "mut": self.mutability.to_json(mir),
+            "mutable": self.mutability == mir::Mutability::Mut,
+            // Temporaries and other locals that don't correspond to a user binding.
+            "internal": self.internal || self.is_user_variable.is_none(),
+            // The name from the source code, for user variables and arguments.  `name` (set by
+            // `local_json`) is always the MIR name, like `_1`.
+            "debug_name": self.name.map(|n| n.to_string()),
             "ty": self.ty.to_json(mir),
             // We specifically record whether the variable's type is zero-sized, because rustc
             // allows reading and taking refs of uninitialized zero-sized locals.
-            "is_zst": mir.state.tcx.layout_of(ty::ParamEnv::reveal_all().and(self.ty))
-                .expect("failed to get layout").is_zst(),
+            "is_zst": layout.is_zst(),
+            "layout": {
+                "size": layout.size.bytes(),
+                "align": layout.align.abi.bytes(),
+                "abi": layout_abi_json(&layout),
+                "uninhabited": layout.abi.is_uninhabited(),
+            },
             "scope": format!("{:?}", self.source_info.scope),
             "pos": pos
         })
@@ -363,9 +548,16 @@ impl<'tcx> ToJson<'tcx> for mir::Statement<'tcx> {
                     "rhs": r.to_json(mir)
                 })
             }
-            &mir::StatementKind::FakeRead { .. } => {
-                // TODO
-                json!({"kind": "FakeRead"})
+            // `FakeRead`, `Retag`, and `AscribeUserType` have no runtime effect, but we still emit
+            // them (as we do `Nop`) so that statement indices match `rustc`'s.  The
+            // `CleanupNonCodegenStatements` pass turns `FakeRead` and `AscribeUserType` into
+            // `Nop`s before optimized MIR is built, so they rarely show up here.
+            &mir::StatementKind::FakeRead(ref cause, ref place) => {
+                json!({
+                    "kind": "FakeRead",
+                    "cause": format!("{:?}", cause),
+                    "place": place.to_json(mir),
+                })
             }
             &mir::StatementKind::SetDiscriminant {
                 ref place,
@@ -377,6 +569,9 @@ impl<'tcx> ToJson<'tcx> for mir::Statement<'tcx> {
                     "variant_index": variant_index.to_json(mir)
                 })
             }
+            // The storage of a local is valid between its `StorageLive` and `StorageDead`.  Locals
+            // declared inside a loop body get a fresh pair on every iteration.  Locals that never
+            // appear in either statement (like arguments) are live for the whole body.
             &mir::StatementKind::StorageLive(l) => {
                 json!({"kind": "StorageLive", "slvar": local_json(mir, l)})
             }
@@ -387,13 +582,19 @@ impl<'tcx> ToJson<'tcx> for mir::Statement<'tcx> {
                 // TODO
                 json!({"kind": "InlineAsm"})
             }
-            &mir::StatementKind::Retag { .. } => {
-                // TODO
-                json!({"kind": "Retag"})
+            &mir::StatementKind::Retag(ref kind, ref place) => {
+                json!({
+                    "kind": "Retag",
+                    "retag_kind": format!("{:?}", kind),
+                    "place": place.to_json(mir),
+                })
             }
-            &mir::StatementKind::AscribeUserType { .. } => {
-                // TODO
-                json!({"kind": "AscribeUserType"})
+            &mir::StatementKind::AscribeUserType(ref place, variance, _) => {
+                json!({
+                    "kind": "AscribeUserType",
+                    "place": place.to_json(mir),
+                    "variance": format!("{:?}", variance),
+                })
             }
             &mir::StatementKind::Nop => {
                 json!({"kind": "Nop"})
@@ -404,10 +605,23 @@ impl<'tcx> ToJson<'tcx> for mir::Statement<'tcx> {
                     .source_map()
                     .span_to_string(self.source_info.span);
         j["pos"] = json!(pos);
+        j["scope"] = json!(format!("{:?}", self.source_info.scope));
         j
     }
 }
 
+/// Render a `SwitchInt` value, given as raw bits, as a decimal number of type `ty`.
+fn switch_value_str<'tcx>(tcx: TyCtxt<'tcx>, ty: ty::Ty<'tcx>, bits: u128) -> String {
+    match ty.sty {
+        ty::TyKind::Int(_) => {
+            let size = tcx.layout_of(ty::ParamEnv::reveal_all().and(ty))
+                .expect("failed to get layout").size;
+            (size.sign_extend(bits) as i128).to_string()
+        },
+        _ => bits.to_string(),
+    }
+}
+
 impl<'tcx> ToJson<'tcx> for mir::TerminatorKind<'tcx> {
     fn to_json(&self, mir: &mut MirState<'_, 'tcx>) -> serde_json::Value {
         match self {
@@ -422,12 +636,22 @@ impl<'tcx> ToJson<'tcx> for mir::TerminatorKind<'tcx> {
             } => {
                 let vals: Vec<String> =
                   values.iter().map(|&c| c.to_string()).collect();
+                // `targets` has one more entry than `values`; the last one is taken when no value
+                // matches.  `cases` pairs up the rest, with each value interpreted according to
+                // `switch_ty`, so negative values of signed types are negative here.  (`values`
+                // holds the raw bits instead.)
+                let cases = values.iter().zip(targets.iter()).map(|(&bits, target)| json!({
+                    "value": switch_value_str(mir.state.tcx, *switch_ty, bits),
+                    "target": target.to_json(mir),
+                })).collect::<Vec<_>>();
                 json!({
                     "kind": "SwitchInt",
                     "discr": discr.to_json(mir),
                     "switch_ty": switch_ty.to_json(mir),
                     "values": vals,
-                    "targets": targets.to_json(mir)
+                    "targets": targets.to_json(mir),
+                    "cases": cases,
+                    "otherwise": targets.last().map(|t| t.to_json(mir)),
                 })
             }
             &mir::TerminatorKind::Resume => {
@@ -436,6 +660,8 @@ impl<'tcx> ToJson<'tcx> for mir::TerminatorKind<'tcx> {
             &mir::TerminatorKind::Return => {
                 json!({"kind": "Return"})
             }
+            // Control can't reach this block, for example after matching on an uninhabited
+            // type.  Unlike `Abort`, reaching it is undefined behavior, not a process exit.
             &mir::TerminatorKind::Unreachable => {
                 json!({"kind": "Unreachable"})
             }
@@ -472,12 +698,27 @@ impl<'tcx> ToJson<'tcx> for mir::TerminatorKind<'tcx> {
                 ref cleanup,
                 ref from_hir_call
             } => {
+                // Calls to compiler intrinsics have no body to step into, so we flag them with
+                // the intrinsic's name.
+                let tcx = mir.state.tcx;
+                let intrinsic = match func.ty(mir.mir.unwrap(), tcx).sty {
+                    ty::TyKind::FnDef(def_id, _) => match tcx.fn_sig(def_id).abi() {
+                        abi::Abi::RustIntrinsic |
+                        abi::Abi::PlatformIntrinsic =>
+                            Some(tcx.item_name(def_id).to_string()),
+                        _ => None,
+                    },
+                    _ => None,
+                };
                 json!({
                     "kind": "Call",
                     "func": func.to_json(mir),
+                    "intrinsic": intrinsic,
                     "args": args.to_json(mir),
                     "destination": destination.to_json(mir),
                     "cleanup": cleanup.to_json(mir),
+                    // Same as `cleanup`, under the name used by `Drop` and `DropAndReplace`.
+                    "unwind": cleanup.to_json(mir),
                     "from_hir_call": from_hir_call
                 })
             }
@@ -494,9 +735,12 @@ impl<'tcx> ToJson<'tcx> for mir::TerminatorKind<'tcx> {
                     "expected": expected,
                     "msg": msg.to_json(mir),
                     "target": target.to_json(mir),
-                    "cleanup": cleanup.to_json(mir)
+                    "cleanup": cleanup.to_json(mir),
+                    "unwind": cleanup.to_json(mir)
                 })
             }
+            // Abort the process.  `rustc` inserts this in cleanup paths where unwinding must not
+            // continue, such as in `#[unwind(aborts)]` functions.
             &mir::TerminatorKind::Abort => {
                 json!({ "kind": "Abort" })
             }
@@ -531,9 +775,12 @@ impl<'tcx> ToJson<'tcx> for mir::BasicBlockData<'tcx> {
         for statement in &self.statements {
             sts.push(statement.to_json(mir));
         }
+        let terminator = self.terminator();
+        let mut term_j = terminator.kind.to_json(mir);
+        term_j["scope"] = json!(format!("{:?}", terminator.source_info.scope));
         json!({
             "data": sts,
-            "terminator": self.terminator().kind.to_json(mir)
+            "terminator": term_j
         })
     }
 }
@@ -567,9 +814,37 @@ fn mir_body(
             }),
         );
     }
+    // Predecessor lists are keyed by block ID, and use the same block names as `blocks`.
+    let mut predecessors = serde_json::Map::new();
+    for (bb, preds) in mir.predecessors().iter_enumerated() {
+        let preds = preds.iter().map(|p| p.to_json(ms)).collect::<Vec<_>>();
+        predecessors.insert(format!("{:?}", bb), preds.into());
+    }
+    // Scopes are named the same way as the `scope` fields of locals, statements, and terminators.
+    // When MIR inlining is enabled, the scopes of an inlined callee are nested under the scope of
+    // the call site, but our `rustc` doesn't record which instance they came from.
+    //
+    // `locals` lists the user variables that come into view in each scope.  A variable is visible
+    // in its scope and all scopes nested inside it, so the variables in view at a statement are
+    // those of its scope and that scope's ancestors.  A shadowing `let` introduces a new scope.
+    let mut scope_locals = vec![Vec::new(); mir.source_scopes.len()];
+    for (local, decl) in mir.local_decls.iter_enumerated() {
+        if decl.name.is_some() {
+            scope_locals[decl.visibility_scope.as_usize()].push(format!("{:?}", local));
+        }
+    }
+    let scopes = mir.source_scopes.iter_enumerated().map(|(scope, data)| json!({
+        "scope": format!("{:?}", scope),
+        "parent": data.parent_scope.map(|p| format!("{:?}", p)),
+        "pos": ms.state.session.source_map().span_to_string(data.span),
+        "locals": scope_locals[scope.as_usize()],
+    })).collect::<Vec<_>>();
     json!({
         "vars": vars,
-        "blocks": blocks
+        "blocks": blocks,
+        "start_block": mir::START_BLOCK.to_json(ms),
+        "predecessors": predecessors,
+        "source_scopes": scopes,
     })
 }
 
@@ -644,9 +919,9 @@ fn emit_traits(ms: &mut MirState, out: &mut impl JsonOutput) -> io::Result<()> {
         if !def_id.is_local() {
             continue;
         }
-        if !tcx.is_object_safe(def_id) {
-            continue;
-        }
+        // Traits that aren't object safe are still emitted, so that `object_safe` can tell
+        // consumers not to expect vtables for them.
+        let object_safe = tcx.is_object_safe(def_id);
 
         let trait_name = def_id_str(tcx, def_id);
         let items = tcx.associated_items(def_id);
@@ -654,10 +929,18 @@ fn emit_traits(ms: &mut MirState, out: &mut impl JsonOutput) -> io::Result<()> {
             format!("Emitting trait items for {}",
                     trait_name).as_str());
         let mut items_json = Vec::new();
+        // The methods of this trait that can be called through a vtable, in declaration order,
+        // which is the order of their vtable slots.  Methods with `where Self: Sized` are left
+        // out.  Supertrait methods aren't included here; they're listed in the entries for the
+        // supertraits.
+        let mut vtable_entries = Vec::new();
         for item in items {
             if !tcx.is_vtable_safe_method(def_id, &item) {
                 continue;
             }
+            if object_safe && item.kind == ty::AssocKind::Method {
+                vtable_entries.push(item.def_id.to_json(ms));
+            }
             items_json.push(assoc_item_json(ms, tcx, &item));
         }
         let supers = traits::supertrait_def_ids(tcx, def_id);
@@ -670,7 +953,12 @@ fn emit_traits(ms: &mut MirState, out: &mut impl JsonOutput) -> io::Result<()> {
         out.emit(EntryKind::Trait, json!({
             "name": def_id.to_json(ms),
             "items": serde_json::Value::Array(items_json),
+            "object_safe": object_safe,
+            "vtable_entries": vtable_entries,
             "supertraits": serde_json::Value::Array(supers_json),
+            // The direct supertrait bounds, with their substitutions (`trait Foo: Bar<u8>`).
+            // `supertraits` gives only the def-ids, but includes indirect supertraits as well.
+            "super_predicates": tcx.super_predicates_of(def_id).to_json(ms),
             "generics": generics.to_json(ms),
             "predicates": preds.to_json(ms),
         }))?;
@@ -679,6 +967,42 @@ fn emit_traits(ms: &mut MirState, out: &mut impl JsonOutput) -> io::Result<()> {
     Ok(())
 }
 
+/// Emit all impl blocks defined in the current crate, both inherent and trait impls.
+fn emit_impls(ms: &mut MirState, out: &mut impl JsonOutput) -> io::Result<()> {
+    let tcx = ms.state.tcx;
+    for item in tcx.hir().krate().items.values() {
+        match item.node {
+            hir::ItemKind::Impl(..) => {},
+            _ => continue,
+        }
+        let def_id = tcx.hir().local_def_id(item.hir_id);
+        let self_ty = tcx.type_of(def_id);
+        let trait_ref = tcx.impl_trait_ref(def_id);
+        let items = tcx.associated_item_def_ids(def_id).iter()
+            .map(|did| did.to_json(ms))
+            .collect::<Vec<_>>();
+        // Associated consts are given in full, since their values are known up front when the
+        // impl is monomorphic.
+        let consts = tcx.associated_items(def_id)
+            .filter(|item| item.kind == ty::AssocKind::Const)
+            .map(|item| assoc_item_json(ms, tcx, &item))
+            .collect::<Vec<_>>();
+        out.emit(EntryKind::Impl, json!({
+            "name": def_id.to_json(ms),
+            "self_ty": self_ty.to_json(ms),
+            // The linker uses `trait` and `self_adt` to decide whether to keep the impl.
+            "trait": trait_ref.map(|tr| tr.def_id.to_json(ms)),
+            "trait_ref": trait_ref.to_json(ms),
+            "self_adt": self_ty.ty_adt_def().map(|adt| adt.did.to_json(ms)),
+            "generics": tcx.generics_of(def_id).to_json(ms),
+            "predicates": tcx.predicates_of(def_id).to_json(ms),
+            "items": items,
+            "consts": consts,
+        }))?;
+    }
+    Ok(())
+}
+
 fn iter_trait_def_ids<'tcx>(
     state: &CompileState<'_, 'tcx>
 ) -> impl Iterator<Item=DefId> + 'tcx {
@@ -703,32 +1027,87 @@ fn emit_statics(ms: &mut MirState, out: &mut impl JsonOutput) -> io::Result<()>
             MonoItem::GlobalAsm(_) => {},
         }
     }
+
+    // `const` items aren't mono items, since they have no address of their own, so we find them
+    // by walking the HIR instead.
+    for item in tcx.hir().krate().items.values() {
+        if let hir::ItemKind::Const(..) = item.node {
+            let def_id = tcx.hir().local_def_id(item.hir_id);
+            if tcx.generics_of(def_id).count() == 0 {
+                emit_const_item(ms, out, def_id)?;
+            }
+        }
+    }
     Ok(())
 }
 
+/// Evaluate the value of a non-generic static or const item.  Returns `None` if evaluation fails;
+/// the error has already been reported by `rustc` in that case.
+fn eval_item<'tcx>(tcx: TyCtxt<'tcx>, def_id: DefId) -> Option<&'tcx ty::Const<'tcx>> {
+    let param_env = ty::ParamEnv::reveal_all();
+    let cid = mir::interpret::GlobalId {
+        instance: ty::Instance::mono(tcx, def_id),
+        promoted: None,
+    };
+    tcx.const_eval(param_env.and(cid)).ok()
+}
+
 fn emit_static(ms: &mut MirState, out: &mut impl JsonOutput, def_id: DefId) -> io::Result<()> {
     let tcx = ms.state.tcx;
     let name = def_id_str(tcx, def_id);
     let mir = tcx.optimized_mir(def_id);
 
     emit_fn(ms, out, &name, None, mir)?;
-    emit_static_decl(ms, out, &name, mir.return_ty(), tcx.is_mutable_static(def_id), None)?;
+    // The initial value of a `static mut` is still meaningful, so we evaluate those too.
+    let value = eval_item(tcx, def_id);
+    let thread_local = tcx.codegen_fn_attrs(def_id).flags
+        .contains(CodegenFnAttrFlags::THREAD_LOCAL);
+    emit_static_decl(ms, out, &name, mir.return_ty(), tcx.is_mutable_static(def_id),
+        thread_local, value, None)?;
     Ok(())
 }
 
-/// Add a new static declaration to `out.statics`.
+/// Emit a `const` item.  Like a static, it gets an entry in `out.fns` for its initializer and one
+/// in `out.statics`, which is marked with `"const": true`.
+fn emit_const_item(ms: &mut MirState, out: &mut impl JsonOutput, def_id: DefId) -> io::Result<()> {
+    let tcx = ms.state.tcx;
+    let name = def_id_str(tcx, def_id);
+    let mir = tcx.optimized_mir(def_id);
+
+    // Emitting the initializer also marks any `const fn`s it calls as used, so their bodies are
+    // exported even if nothing calls them at runtime.
+    emit_fn(ms, out, &name, None, mir)?;
+    let value = eval_item(tcx, def_id).map(|c| c.to_json(ms));
+    out.emit(EntryKind::Static, json!({
+        "name": name,
+        "ty": mir.return_ty().to_json(ms),
+        "mutable": false,
+        "thread_local": false,
+        "const": true,
+        "value": value,
+    }))
+}
+
+/// Add a new static declaration to `out.statics`.  `const` items are emitted by `emit_const_item`
+/// instead.
 fn emit_static_decl<'tcx>(
     ms: &mut MirState<'_, 'tcx>,
     out: &mut impl JsonOutput,
     name: &str,
     ty: ty::Ty<'tcx>,
     mutable: bool,
+    thread_local: bool,
+    value: Option<&'tcx ty::Const<'tcx>>,
     promoted_info: Option<(&str, usize)>,
 ) -> io::Result<()> {
     let mut j = json!({
         "name": name,
         "ty": ty.to_json(ms),
         "mutable": mutable,
+        "thread_local": thread_local,
+        "const": false,
+        // `null` if the static couldn't be evaluated, as for `const` items.
+        "value": value.map(|v| v.to_json(ms)),
     });
     if let Some((parent, idx)) = promoted_info {
         j.as_object_mut().unwrap().insert("promoted_from".to_owned(), parent.into());
@@ -738,6 +1117,66 @@ fn emit_static_decl<'tcx>(
 }
 
 
+/// Built-in attributes that are exported with each function.  Tool attributes (such as
+/// `#[crux::test]`) are always exported.
+const EXPORTED_ATTRS: &[&str] = &[
+    "crux_test",
+    "test",
+    "should_panic",
+    "no_mangle",
+    "inline",
+    "cold",
+];
+
+fn attr_json(attr: &ast::Attribute) -> serde_json::Value {
+    let name = attr.path.segments.iter()
+        .map(|seg| seg.ident.to_string())
+        .collect::<Vec<_>>()
+        .join("::");
+    let args = match attr.meta().map(|m| m.node) {
+        Some(ast::MetaItemKind::List(ref items)) =>
+            items.iter().map(pprust::meta_list_item_to_string).collect(),
+        Some(ast::MetaItemKind::NameValue(ref lit)) => vec![lit.token.to_string()],
+        Some(ast::MetaItemKind::Word) => vec![],
+        // Tool attributes can contain arbitrary tokens that don't parse as a meta item.
+        None => vec![pprust::tts_to_string(attr.tokens.clone())],
+    };
+    json!({
+        "name": name,
+        "args": args,
+    })
+}
+
+fn attrs_json(tcx: TyCtxt, def_id: DefId) -> serde_json::Value {
+    let attrs = tcx.get_attrs(def_id);
+    attrs.iter()
+        .filter(|attr| {
+            attr.path.segments.len() > 1 ||
+                EXPORTED_ATTRS.iter().any(|&name| attr.check_name(Symbol::intern(name)))
+        })
+        .map(attr_json)
+        .collect::<Vec<_>>()
+        .into()
+}
+
+/// Get the argument positions listed in `#[rustc_args_required_const(..)]`.  Arguments at these
+/// positions must be constants, even though they're passed as ordinary operands.
+fn args_required_const(tcx: TyCtxt, def_id: DefId) -> Vec<usize> {
+    let attrs = tcx.get_attrs(def_id);
+    let attr = match attrs.iter().find(|a| a.check_name(Symbol::intern("rustc_args_required_const"))) {
+        Some(x) => x,
+        None => return vec![],
+    };
+    let items = attr.meta_item_list().unwrap_or_else(Vec::new);
+    items.iter().filter_map(|item| match item.literal() {
+        Some(&ast::Lit { node: ast::LitKind::Int(n, _), .. }) => Some(n as usize),
+        _ => {
+            eprintln!("error: unexpected argument {:?} in rustc_args_required_const", item);
+            None
+        },
+    }).collect()
+}
+
 fn has_test_attr(tcx: TyCtxt, def_id: DefId) -> bool {
     def_id.is_local() && tcx.has_attr(def_id, Symbol::intern("crux_test"))
 }
@@ -769,9 +1208,10 @@ fn init_instances_from_mono_items(ms: &mut MirState) -> io::Result<()> {
     Ok(())
 }
 
-/// Initialize the set of needed instances.  Returns a list of root instances.
-fn init_instances_from_tests(ms: &mut MirState, out: &mut impl JsonOutput) -> io::Result<()> {
-    let tcx = ms.state.tcx;
+/// Find the test functions in the current crate.  Items with the test attribute that can't be
+/// tests are reported as errors and skipped.
+fn test_fns(tcx: TyCtxt) -> Vec<DefId> {
+    let mut tests = Vec::new();
     for &def_id in tcx.mir_keys(def_id::LOCAL_CRATE) {
         if !has_test_attr(tcx, def_id) {
             continue;
@@ -791,7 +1231,16 @@ fn init_instances_from_tests(ms: &mut MirState, out: &mut impl JsonOutput) -> io
             );
             continue;
         }
+        tests.push(def_id);
+    }
+    tests
+}
 
+/// Initialize the set of needed instances.  Returns a list of root instances.
+fn init_instances_from_tests(ms: &mut MirState, out: &mut impl JsonOutput) -> io::Result<()> {
+    let tcx = ms.state.tcx;
+    let mut tests = Vec::new();
+    for def_id in test_fns(tcx) {
         let inst = ty::Instance::resolve(tcx, ty::ParamEnv::reveal_all(), def_id, List::empty())
             .unwrap_or_else(|| {
                 panic!("Instance::resolve failed to find test function {:?}?", def_id);
@@ -799,10 +1248,56 @@ fn init_instances_from_tests(ms: &mut MirState, out: &mut impl JsonOutput) -> io
 
         ms.used.instances.insert(inst);
         out.add_root(inst_id_str(tcx, inst))?;
+        tests.push(inst_id_str(tcx, inst));
     }
+    tests.sort();
+    out.add_crate_info("tests", json!(tests))?;
     Ok(())
 }
 
+/// Describe the crate's entry point, if it has one: the `main` function (or `#[start]` function)
+/// of a binary crate.  `instance` is the name of its entry in `fns`.
+fn entry_fn_json(ms: &mut MirState) -> serde_json::Value {
+    let tcx = ms.state.tcx;
+    match tcx.entry_fn(LOCAL_CRATE) {
+        Some((def_id, kind)) => json!({
+            "def_id": def_id.to_json(ms),
+            "instance": inst_id_str(tcx, ty::Instance::mono(tcx, def_id)),
+            "kind": match kind {
+                EntryFnType::Main => "Main",
+                EntryFnType::Start => "Start",
+            },
+        }),
+        None => serde_json::Value::Null,
+    }
+}
+
+/// Map the name of each lang item (like `sized` or `box_free`) to the item that implements it,
+/// across this crate and all its dependencies.  Lang items the crate graph doesn't define are
+/// omitted.
+fn lang_items_json(ms: &mut MirState) -> serde_json::Value {
+    let tcx = ms.state.tcx;
+    let mut map = serde_json::Map::new();
+    for &def_id in tcx.lang_items().items().iter().filter_map(|x| x.as_ref()) {
+        // `LanguageItems` doesn't expose the names of its items, so we read them from the
+        // `#[lang]` attribute (or `#[panic_handler]` and the like) just as `rustc` does.
+        if let Some((name, _)) = lang_items::extract(&tcx.get_attrs(def_id)) {
+            map.insert(name.to_string(), def_id.to_json(ms));
+        }
+    }
+    map.into()
+}
+
+
+fn is_ffi_abi(abi: abi::Abi) -> bool {
+    match abi {
+        abi::Abi::Rust |
+        abi::Abi::RustCall |
+        abi::Abi::RustIntrinsic |
+        abi::Abi::PlatformIntrinsic => false,
+        _ => true,
+    }
+}
 
 /// Add a single `Instance` to `out.fns` and/or `out.intrinsics`, depending on its kind.
 fn emit_instance<'tcx>(
@@ -817,10 +1312,29 @@ fn emit_instance<'tcx>(
     // We actually record every instance in `intrinsics`, not just `InstanceDef::Intrinsic` and
     // other special functions, because the intrinsics table is used to look up CustomOps.
     // (CustomOps are keyed on the pre-monomorphization name of the function.)
-    out.emit(EntryKind::Intrinsic, json!({
+    let mut j = json!({
         "name": &name,
         "inst": inst.to_json(ms),
-    }))?;
+        // Lets consumers route shims and drop glue without looking inside `inst`.
+        "instance_kind": inst_kind_str(inst),
+        "required_const_args": args_required_const(tcx, inst_def_id(inst)),
+        "call_kind": call_kind_json(tcx, inst),
+    });
+    // Functions with a non-Rust ABI (including foreign functions, which have no body and so no
+    // entry in `fns`) also record how their arguments and return value are passed, in the same
+    // `fn_abi` form as `fns` entries use.
+    // (`Item` instances may also be consts or closures, which don't have an ABI of their own.)
+    if let ty::InstanceDef::Item(def_id) = inst.def {
+        match tcx.def_kind(def_id) {
+            Some(DefKind::Fn) | Some(DefKind::Method) => {
+                if is_ffi_abi(tcx.fn_sig(def_id).abi()) {
+                    j["fn_abi"] = json!(fn_abi_json(ms, inst));
+                }
+            },
+            _ => {},
+        }
+    }
+    out.emit(EntryKind::Intrinsic, j)?;
 
     let def_id = match inst.def {
         ty::InstanceDef::Item(def_id) => def_id,
@@ -862,6 +1376,10 @@ fn emit_vtable<'tcx>(
     out.emit(EntryKind::Vtable, json!({
         "name": vtable_name(ms, trait_ref),
         "desc": trait_ref.skip_binder().to_json(ms),
+        // The vtable is determined by the concrete type and the trait.  These are also present in
+        // `desc`, but are broken out here for convenience.
+        "self_ty": trait_ref.skip_binder().self_ty().to_json(ms),
+        "trait": trait_ref.def_id().to_json(ms),
         "items": build_vtable_items(ms, trait_ref),
     }))
 }
@@ -895,6 +1413,7 @@ fn build_vtable_items<'tcx>(
         mir.used.instances.insert(inst);
         parts.push(json!({
             "def_id": inst_id_str(mir.state.tcx, inst),
+            "method": def_id.to_json(mir),
             "instance": inst.to_json(mir),
         }));
     }
@@ -920,7 +1439,10 @@ fn emit_adt<'tcx>(
 }
 
 
-/// Output a MIR body to `out.fns`.  Recursively emits all promoted statics from the body.
+/// Output a MIR body to `out.fns`.  Recursively emits all promoted statics from the body.  Each
+/// promoted gets its own `fns` entry, with a full MIR body, named
+/// `<name>::{{promoted}}[<index>]`.  The function's `promoted` field lists these names in order of
+/// promoted index, so `Promoted` places in the body can be resolved against it.
 fn emit_fn<'tcx>(
     ms: &mut MirState<'_, 'tcx>,
     out: &mut impl JsonOutput,
@@ -933,7 +1455,9 @@ fn emit_fn<'tcx>(
     let mut ms = MirState {
         mir: Some(mir),
         used: ms.used,
+        unsupported: ms.unsupported,
         state: ms.state,
+        pretty_types: ms.pretty_types,
     };
     let ms = &mut ms;
 
@@ -941,16 +1465,22 @@ fn emit_fn<'tcx>(
     for (idx, prom_mir) in mir.promoted.iter_enumerated() {
         let prom_name = format!("{}::{{{{promoted}}}}[{}]", name, idx.as_usize());
         emit_fn(ms, out, &prom_name, None, prom_mir)?;
-        emit_static_decl(ms, out, &prom_name, prom_mir.return_ty(), false,
+        emit_static_decl(ms, out, &prom_name, prom_mir.return_ty(), false, false, None,
             Some((name, idx.as_usize())))?;
         promoted.push(prom_name);
     }
 
     let abi = inst.map(|i| inst_abi(ms.state.tcx, i)).unwrap_or(abi::Abi::Rust);
+    let span = match inst {
+        Some(i) => ms.state.tcx.def_span(inst_def_id(i)),
+        None => mir.span,
+    };
 
+    let is_const_fn = inst.map(|i| ms.state.tcx.is_const_fn(inst_def_id(i)));
     out.emit(EntryKind::Fn, json!({
         "name": &name,
         "inst": inst.to_json(ms),
+        "instance_kind": inst.map(inst_kind_str),
         "args": mir.args_iter().map(|l| local_json(ms, l)).collect::<Vec<_>>(),
         "return_ty": mir.return_ty().to_json(ms),
         "generics": { "params": [] },
@@ -958,10 +1488,76 @@ fn emit_fn<'tcx>(
         "body": mir_body(ms),
         "promoted": promoted,
         "abi": abi.to_json(ms),
+        "fn_abi": inst.and_then(|i| fn_abi_json(ms, i)),
+        "call_kind": inst.map(|i| call_kind_json(ms.state.tcx, i)),
         "spread_arg": mir.spread_arg.map(|x| x.as_usize()),
+        "span": span.to_json(ms),
+        "attrs": inst.map(|i| attrs_json(ms.state.tcx, inst_def_id(i))),
+        "projections": inst.map(|i| resolved_projections(ms, i)),
+        "is_const": is_const_fn,
+        // Same as `is_const`, under the name used by `tcx.is_const_fn`.
+        "is_const_fn": is_const_fn,
     }))
 }
 
+/// For each associated type projection (like `<I as Iterator>::Item`) that appears in the types of
+/// the generic MIR for `inst`, record the concrete type it resolves to in this instance.
+/// Projections that still depend on type parameters after substitution are skipped.
+fn resolved_projections<'tcx>(
+    ms: &mut MirState<'_, 'tcx>,
+    inst: ty::Instance<'tcx>,
+) -> serde_json::Value {
+    let tcx = ms.state.tcx;
+    let generic_mir = tcx.optimized_mir(inst_def_id(inst));
+
+    let mut projs = Vec::new();
+    for decl in generic_mir.local_decls.iter() {
+        for t in decl.ty.walk() {
+            if let ty::TyKind::Projection(_) = t.sty {
+                if !projs.contains(&t) {
+                    projs.push(t);
+                }
+            }
+        }
+    }
+
+    let mut entries = Vec::with_capacity(projs.len());
+    for proj in projs {
+        let substituted = tcx.erase_regions(&proj.subst(tcx, inst.substs));
+        if substituted.needs_subst() || substituted.has_escaping_bound_vars() {
+            continue;
+        }
+        // `normalize_erasing_regions` panics if it can't normalize the projection, which happens
+        // when the trait isn't actually implemented (possible with `#![feature(trivial_bounds)]`).
+        if !projections_hold(tcx, substituted) {
+            continue;
+        }
+        let normalized = tcx.normalize_erasing_regions(ty::ParamEnv::reveal_all(), substituted);
+        entries.push(json!({
+            "projection": substituted.to_json(ms),
+            "ty": normalized.to_json(ms),
+        }));
+    }
+    entries.into()
+}
+
+/// Check that the trait bound behind every projection in `ty` holds, so that `ty` can be
+/// normalized.  `ty` must be fully monomorphic.
+fn projections_hold<'tcx>(tcx: TyCtxt<'tcx>, ty: ty::Ty<'tcx>) -> bool {
+    ty.walk().all(|t| match t.sty {
+        ty::TyKind::Projection(ref proj) => {
+            let trait_ref = ty::Binder::bind(proj.trait_ref(tcx));
+            let obligation = traits::Obligation::new(
+                traits::ObligationCause::dummy(),
+                ty::ParamEnv::reveal_all(),
+                trait_ref.to_predicate(),
+            );
+            tcx.infer_ctxt().enter(|infcx| infcx.predicate_must_hold_modulo_regions(&obligation))
+        },
+        _ => true,
+    })
+}
+
 fn inst_abi<'tcx>(
     tcx: TyCtxt<'tcx>,
     inst: ty::Instance<'tcx>,
@@ -982,11 +1578,78 @@ fn inst_abi<'tcx>(
     }
 }
 
+/// Classify how `inst` is called: `Rust` for ordinary functions, `RustCall` for closure bodies
+/// and `Fn*` trait shims (which take their arguments as a tuple), `Intrinsic` (with the
+/// intrinsic's `name`) for compiler intrinsics, and `Foreign` (with the `abi`) for everything else.
+fn call_kind_json<'tcx>(
+    tcx: TyCtxt<'tcx>,
+    inst: ty::Instance<'tcx>,
+) -> serde_json::Value {
+    if let ty::InstanceDef::Intrinsic(def_id) = inst.def {
+        return json!({"kind": "Intrinsic", "name": tcx.item_name(def_id).to_string()});
+    }
+    match inst_abi(tcx, inst) {
+        abi::Abi::Rust => json!({"kind": "Rust"}),
+        abi::Abi::RustCall => json!({"kind": "RustCall"}),
+        abi::Abi::RustIntrinsic |
+        abi::Abi::PlatformIntrinsic => json!({
+            "kind": "Intrinsic",
+            "name": tcx.item_name(inst_def_id(inst)).to_string(),
+        }),
+        abi => json!({"kind": "Foreign", "abi": abi.name()}),
+    }
+}
+
+
+/// Bundle the contents of the source files that our spans point into.  Only files whose source
+/// text is loaded in the `SourceMap` can be bundled; in practice, this means the files of the
+/// local crate.
+fn emit_source_files(
+    ms: &mut MirState,
+    out: &mut impl JsonOutput,
+) -> io::Result<()> {
+    let source_map = ms.state.session.source_map();
+    let mut files = serde_json::Map::new();
+    for file in source_map.files().iter() {
+        if !file.name.is_real() {
+            continue;
+        }
+        if let Some(ref src) = file.src {
+            files.insert(file.name.to_string(), json!(**src));
+        }
+    }
+    out.add_crate_info("source_files", files.into())
+}
+
+/// Extra `rustc` arguments for the MIR optimization level requested by `MIR_JSON_OPT_LEVEL`.
+/// We always export each item's `optimized_mir`: the earlier bodies are consumed while building
+/// it, and other crates' metadata only contains the optimized one.  So instead of picking an
+/// earlier body, we choose which passes build it.  Level 0 skips all the optional optimizations,
+/// giving the most literal body available, and level 2 enables more (including inlining).
+/// Required passes, such as drop elaboration, run at every level.
+pub fn mir_opt_level_args() -> Vec<String> {
+    let s = match env::var("MIR_JSON_OPT_LEVEL") {
+        Ok(s) => s,
+        Err(_) => return Vec::new(),
+    };
+    match s.parse::<usize>() {
+        Ok(level) => vec!["-Z".into(), format!("mir-opt-level={}", level)],
+        Err(_) => {
+            eprintln!("warning: ignoring invalid MIR_JSON_OPT_LEVEL {:?}", s);
+            Vec::new()
+        },
+    }
+}
 
+/// The result of exporting a crate's MIR.  The `analyze` functions return `Ok(None)` instead of
+/// this when the build doesn't produce an `Exe` output (for example, `cargo check` builds, which
+/// only emit metadata), since there's no output path to put a `.mir` file next to.
 #[derive(Debug)]
 pub struct AnalysisData<O> {
     pub mir_path: PathBuf,
     pub extern_mir_paths: Vec<PathBuf>,
+    /// Whether the crate is `#![no_std]`.
+    pub no_std: bool,
     pub output: O,
 }
 
@@ -1000,6 +1663,7 @@ fn analyze_inner<O: JsonOutput, F: FnOnce(&Path) -> io::Result<O>>(
 ) -> Result<Option<AnalysisData<O>>, serde_cbor::Error> {
     let mut mir_path = None;
     let mut extern_mir_paths = Vec::new();
+    let mut no_std = false;
     let mut gcx = comp.global_ctxt().unwrap().peek_mut();
     let output = gcx.enter(|tcx| -> io::Result<_> {
         let outputs = tcx.output_filenames(LOCAL_CRATE);
@@ -1012,7 +1676,15 @@ fn analyze_inner<O: JsonOutput, F: FnOnce(&Path) -> io::Result<O>>(
             &outputs,
             &tcx.crate_name.to_string(),
         ).with_extension("mir");
-        let mut out = mk_output(&mir_path_)?;
+        no_std = attr::contains_name(tcx.hir().krate_attrs(), Symbol::intern("no_std"));
+
+        let mut out = validate::Validate {
+            inner: lib_util::RoundTripCheck {
+                inner: mk_output(&mir_path_)?,
+                enabled: env::var("MIR_JSON_CHECK_ROUNDTRIP").is_ok(),
+            },
+            enabled: env::var("MIR_JSON_VALIDATE").is_ok(),
+        };
         mir_path = Some(mir_path_);
 
         for &cnum in tcx.all_crate_nums(LOCAL_CRATE) {
@@ -1022,6 +1694,10 @@ fn analyze_inner<O: JsonOutput, F: FnOnce(&Path) -> io::Result<O>>(
                 .chain(src.rmeta.iter());
             for &(ref path, _) in it {
                 let mir_path = path.with_extension("mir");
+                // With `MIR_JSON_SPLIT`, dependencies were exported as split directories instead.
+                let mir_path = lib_util::split_dir(&mir_path)
+                    .filter(|dir| dir.is_dir())
+                    .unwrap_or(mir_path);
                 if mir_path.exists() {
                     extern_mir_paths.push(mir_path);
                     // Add only one copy of the MIR for a crate, even when we have multiple
@@ -1033,18 +1709,17 @@ fn analyze_inner<O: JsonOutput, F: FnOnce(&Path) -> io::Result<O>>(
 
 
         let mut used = Used::default();
+        let mut unsupported = Unsupported::default();
         let state = CompileState {
             session: comp.session(),
             tcx,
         };
-        let mut ms = MirState {
-            mir: None,
-            used: &mut used,
-            state: &state,
-        };
+        let mut ms = MirState::new(&state, &mut used, &mut unsupported);
+        ms.pretty_types = env::var("MIR_JSON_PRETTY_TYPES").is_ok();
 
         // Traits and top-level statics can be enumerated directly.
         emit_traits(&mut ms, &mut out)?;
+        emit_impls(&mut ms, &mut out)?;
         emit_statics(&mut ms, &mut out)?;
 
         // Everything else is demand-driven, to handle monomorphization.  We start with all #[test]
@@ -1063,7 +1738,26 @@ fn analyze_inner<O: JsonOutput, F: FnOnce(&Path) -> io::Result<O>>(
             }
         }
 
-        Ok(Some(out))
+        if env::var("MIR_JSON_SOURCE_FILES").is_ok() {
+            emit_source_files(&mut ms, &mut out)?;
+        }
+        // Every ADT definition we emitted adds the ADTs mentioned in its fields to `used.types`,
+        // so by the time the loop above finishes, this set is closed under field references.
+        let mut referenced_types = ms.used.types.iter()
+            .map(|&def_id| def_id_str(tcx, def_id))
+            .collect::<Vec<_>>();
+        referenced_types.sort();
+        out.add_crate_info("referenced_types", json!(referenced_types))?;
+        out.add_crate_info("unsupported_predicates", json!(ms.unsupported.predicates))?;
+        // With `panic = "abort"`, the `no_landing_pads` pass has already removed all cleanup edges
+        // from the MIR, so this is informational only.
+        out.add_crate_info("panic_strategy", json!(tcx.sess.panic_strategy().desc()))?;
+        out.add_crate_info("data_layout", data_layout_json(tcx))?;
+        out.add_crate_info("entry_fn", entry_fn_json(&mut ms))?;
+        out.add_crate_info("lang_items", lang_items_json(&mut ms))?;
+        out.add_crate_info("mir_opt_level", json!(tcx.sess.opts.debugging_opts.mir_opt_level))?;
+
+        Ok(Some(out.inner.inner))
     })?;
 
     let mir_path = match mir_path {
@@ -1073,47 +1767,231 @@ fn analyze_inner<O: JsonOutput, F: FnOnce(&Path) -> io::Result<O>>(
     // `output` should be `Some` if `mir_path` was `Some`.
     let output = output.unwrap();
 
-    Ok(Some(AnalysisData { mir_path, extern_mir_paths, output }))
+    Ok(Some(AnalysisData { mir_path, extern_mir_paths, no_std, output }))
 }
 
-pub fn analyze_nonstreaming(comp: &Compiler) -> Result<Option<AnalysisData<()>>, serde_cbor::Error> {
-    let opt_ad = analyze_inner(comp, |_| { Ok(lib_util::Output::default()) })?;
-    let AnalysisData { mir_path, extern_mir_paths, output: out } = match opt_ad {
-        Some(x) => x,
-        None => return Ok(None),
-    };
-
-    let total_items = out.fns.len() + out.adts.len() + out.statics.len() + out.vtables.len() +
-        out.traits.len() + out.intrinsics.len();
-    let j = json!({
+/// Build the crate JSON object from the collected output.
+fn crate_json(out: lib_util::Output) -> serde_json::Value {
+    let mut j = json!({
         "fns": out.fns,
         "adts": out.adts,
         "statics": out.statics,
         "vtables": out.vtables,
         "traits": out.traits,
         "intrinsics": out.intrinsics,
-        "impls": [],
+        "impls": out.impls,
+        "version": lib_util::SCHEMA_VERSION,
         "roots": out.roots,
     });
+    for (key, val) in out.crate_info {
+        j[key] = val;
+    }
+    j
+}
+
+/// An error that occurred while exporting the MIR of a crate.
+#[derive(Debug)]
+pub struct AnalysisError {
+    pub crate_name: String,
+    pub source: serde_cbor::Error,
+}
+
+impl fmt::Display for AnalysisError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "failed to export MIR for crate `{}`: {}", self.crate_name, self.source)
+    }
+}
+
+impl Error for AnalysisError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        Some(&self.source)
+    }
+}
+
+/// Attach the name of the crate being compiled to an error from one of the `analyze` functions.
+fn with_crate_name<T>(
+    comp: &Compiler,
+    result: Result<T, serde_cbor::Error>,
+) -> Result<T, AnalysisError> {
+    result.map_err(|source| AnalysisError {
+        crate_name: comp.crate_name().map(|q| q.peek().clone())
+            .unwrap_or_else(|_| "<unknown>".into()),
+        source,
+    })
+}
+
+pub fn analyze_nonstreaming(comp: &Compiler) -> Result<Option<AnalysisData<()>>, AnalysisError> {
+    with_crate_name(comp, analyze_nonstreaming_inner(comp))
+}
+
+fn analyze_nonstreaming_inner(
+    comp: &Compiler,
+) -> Result<Option<AnalysisData<()>>, serde_cbor::Error> {
+    let opt_ad = analyze_inner(comp, |_| { Ok(lib_util::Output::default()) })?;
+    let AnalysisData { mir_path, extern_mir_paths, no_std, output: out } = match opt_ad {
+        Some(x) => x,
+        None => return Ok(None),
+    };
+
+    let total_items = out.fns.len() + out.adts.len() + out.statics.len() + out.vtables.len() +
+        out.traits.len() + out.intrinsics.len() + out.impls.len();
+    let j = crate_json(out);
     comp.session().note_without_error(
         &format!("Indexing MIR ({} items)...", total_items));
     let file = File::create(&mir_path)?;
-    lib_util::write_indexed_crate(file, &j)?;
+    let hash = lib_util::write_indexed_crate(file, &j)?;
+    lib_util::write_hash_file(&mir_path, &hash)?;
 
-    Ok(Some(AnalysisData { mir_path, extern_mir_paths, output: () }))
+    Ok(Some(AnalysisData { mir_path, extern_mir_paths, no_std, output: () }))
 }
 
-pub fn analyze_streaming(comp: &Compiler) -> Result<Option<AnalysisData<()>>, serde_cbor::Error> {
+/// Write the crate JSON to stdout, in the plain (non-indexed) format, instead of writing a `.mir`
+/// file.  Diagnostics and progress messages all go to stderr, so stdout contains only the JSON.
+pub fn analyze_to_stdout(comp: &Compiler) -> Result<Option<AnalysisData<()>>, AnalysisError> {
+    with_crate_name(comp, analyze_to_stdout_inner(comp))
+}
+
+fn analyze_to_stdout_inner(
+    comp: &Compiler,
+) -> Result<Option<AnalysisData<()>>, serde_cbor::Error> {
+    let opt_ad = analyze_inner(comp, |_| { Ok(lib_util::Output::default()) })?;
+    let AnalysisData { mir_path, extern_mir_paths, no_std, output: out } = match opt_ad {
+        Some(x) => x,
+        None => return Ok(None),
+    };
+
+    let j = crate_json(out);
+    let stdout = io::stdout();
+    let mut lock = stdout.lock();
+    lib_util::write_json(&mut lock, &j, lib_util::pretty_json_enabled())
+        .map_err(|e| -> io::Error { e.into() })?;
+    writeln!(lock)?;
+    lock.flush()?;
+
+    Ok(Some(AnalysisData { mir_path, extern_mir_paths, no_std, output: () }))
+}
+
+/// List the test functions in the crate being compiled, without exporting any MIR.  Each test is
+/// described by its `def_path` (the name used in the MIR JSON output), its plain `path`, and its
+/// `span`.
+pub fn list_tests(comp: &Compiler) -> serde_json::Value {
+    let mut gcx = comp.global_ctxt().unwrap().peek_mut();
+    gcx.enter(|tcx| {
+        let source_map = tcx.sess.source_map();
+        let mut tests = test_fns(tcx).into_iter().map(|def_id| json!({
+            "def_path": def_id_str(tcx, def_id),
+            "path": tcx.def_path_str(def_id),
+            "span": source_map.span_to_string(tcx.def_span(def_id)),
+        })).collect::<Vec<_>>();
+        tests.sort_by(|a, b| a["def_path"].as_str().cmp(&b["def_path"].as_str()));
+        tests.into()
+    })
+}
+
+/// Serialize the MIR of a single function from the crate being compiled, along with every ADT
+/// its body refers to (transitively).  `def_path` may be either the name used in the MIR JSON
+/// output (`foo/3a1fbbbh::bar[0]`) or a plain path like `bar` or `m::bar`.  Functions called by
+/// the item are not included.  Returns `None` if there is no matching non-generic function.
+pub fn export_item(comp: &Compiler, def_path: &str) -> io::Result<Option<serde_json::Value>> {
+    let mut gcx = comp.global_ctxt().unwrap().peek_mut();
+    gcx.enter(|tcx| -> io::Result<_> {
+        let def_id = tcx.body_owners().find(|&def_id| {
+            match tcx.def_kind(def_id) {
+                Some(DefKind::Fn) | Some(DefKind::Method) => {},
+                _ => return false,
+            }
+            tcx.generics_of(def_id).count() == 0 &&
+                (def_id_str(tcx, def_id) == def_path || tcx.def_path_str(def_id) == def_path)
+        });
+        let def_id = match def_id {
+            Some(x) => x,
+            None => return Ok(None),
+        };
+        let inst = match ty::Instance::resolve(
+                tcx, ty::ParamEnv::reveal_all(), def_id, List::empty()) {
+            Some(x) => x,
+            None => return Ok(None),
+        };
+
+        let mut out = lib_util::Output::default();
+        let mut used = Used::default();
+        let mut unsupported = Unsupported::default();
+        let state = CompileState {
+            session: comp.session(),
+            tcx,
+        };
+        let mut ms = MirState::new(&state, &mut used, &mut unsupported);
+
+        emit_instance(&mut ms, &mut out, inst)?;
+        out.add_root(inst_id_str(tcx, inst))?;
+        // Only ADTs are followed here; other instances and vtables discovered while emitting the
+        // function are left out.
+        while ms.used.types.has_new() {
+            for adt in ms.used.types.take_new() {
+                emit_adt(&mut ms, &mut out, adt)?;
+            }
+        }
+
+        Ok(Some(crate_json(out)))
+    })
+}
+
+pub fn analyze_streaming(comp: &Compiler) -> Result<Option<AnalysisData<()>>, AnalysisError> {
+    with_crate_name(comp, analyze_streaming_inner(comp))
+}
+
+fn analyze_streaming_inner(
+    comp: &Compiler,
+) -> Result<Option<AnalysisData<()>>, serde_cbor::Error> {
     let opt_ad = analyze_inner(comp, lib_util::start_streaming)?;
-    let AnalysisData { mir_path, extern_mir_paths, output } = match opt_ad {
+    let AnalysisData { mir_path, extern_mir_paths, no_std, output } = match opt_ad {
+        Some(x) => x,
+        None => return Ok(None),
+    };
+    let hash = lib_util::finish_streaming(output)?;
+    lib_util::write_hash_file(&mir_path, &hash)?;
+    Ok(Some(AnalysisData { mir_path, extern_mir_paths, no_std, output: () }))
+}
+
+/// Write the crate's MIR as a directory of files, one per function, instead of as a single `.mir`
+/// file.  The directory is named by `lib_util::split_dir`, and contains `crate.json` (everything
+/// but the functions), `index.json` (mapping each function name to its file), and the function
+/// files.  The returned `mir_path` is the directory.
+pub fn analyze_split(comp: &Compiler) -> Result<Option<AnalysisData<()>>, AnalysisError> {
+    with_crate_name(comp, analyze_split_inner(comp))
+}
+
+fn analyze_split_inner(
+    comp: &Compiler,
+) -> Result<Option<AnalysisData<()>>, serde_cbor::Error> {
+    let opt_ad = analyze_inner(comp, |mir_path| {
+        let dir = lib_util::split_dir(mir_path)
+            .expect("analyze_split requires MIR_JSON_SPLIT to be set");
+        lib_util::start_split(&dir)
+    })?;
+    let AnalysisData { extern_mir_paths, no_std, output, .. } = match opt_ad {
         Some(x) => x,
         None => return Ok(None),
     };
-    lib_util::finish_streaming(output)?;
-    Ok(Some(AnalysisData { mir_path, extern_mir_paths, output: () }))
+    let dir = output.dir().to_owned();
+    let rest = lib_util::finish_split(output)?;
+    let j = crate_json(rest);
+    let mut f = io::BufWriter::new(File::create(dir.join("crate.json"))?);
+    lib_util::write_json(&mut f, &j, lib_util::pretty_json_enabled())
+        .map_err(|e| -> io::Error { e.into() })?;
+    f.flush()?;
+    Ok(Some(AnalysisData { mir_path: dir, extern_mir_paths, no_std, output: () }))
 }
 
-pub use self::analyze_streaming as analyze;
+/// Export the MIR of the crate being compiled, to a `.mir` file next to the crate's output, or to
+/// a split directory if `MIR_JSON_SPLIT` is set.
+pub fn analyze(comp: &Compiler) -> Result<Option<AnalysisData<()>>, AnalysisError> {
+    if env::var("MIR_JSON_SPLIT").is_ok() {
+        analyze_split(comp)
+    } else {
+        analyze_streaming(comp)
+    }
+}
 
 // format:
 // top: function name || function args || return ty || body
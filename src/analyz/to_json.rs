@@ -3,6 +3,7 @@ use rustc::mir::Body;
 use rustc::session::Session;
 use rustc::ty::{self, TyCtxt};
 use rustc_interface::interface::Compiler;
+use syntax::source_map::Span;
 use syntax::symbol::Symbol;
 use serde_json;
 use std::collections::BTreeMap;
@@ -82,10 +83,38 @@ impl<'tcx> Used<'tcx> {
     }
 }
 
+/// Tallies of constructs that we don't serialize faithfully yet.  These are reported in the crate
+/// output, to show how lossy the export is for a given crate.
+#[derive(Default, Debug)]
+pub struct Unsupported {
+    /// Number of occurrences of each predicate kind that was emitted as `"unknown_pred"`.
+    pub predicates: BTreeMap<String, usize>,
+}
+
 pub struct MirState<'a, 'tcx : 'a> {
     pub mir: Option<&'tcx Body<'tcx>>,
     pub used: &'a mut Used<'tcx>,
+    pub unsupported: &'a mut Unsupported,
     pub state: &'a CompileState<'a, 'tcx>,
+    /// Debugging aid: if set, each serialized type also gets a `pretty` field with its
+    /// user-facing rendering, like `std::vec::Vec<u8>`.
+    pub pretty_types: bool,
+}
+
+impl<'a, 'tcx> MirState<'a, 'tcx> {
+    /// Create a `MirState` for serializing outside of a full crate analysis, such as when testing
+    /// individual `ToJson` impls.  `state` needs only the `Session` and `TyCtxt`.  `used` and
+    /// `unsupported` collect the items and unsupported constructs seen during serialization, and
+    /// can start out empty.  `mir` starts as `None`, which suffices for types; set it to the
+    /// enclosing body before serializing statements, rvalues, or places, since their impls look
+    /// up local types there.  `pretty_types` starts out unset.
+    pub fn new(
+        state: &'a CompileState<'a, 'tcx>,
+        used: &'a mut Used<'tcx>,
+        unsupported: &'a mut Unsupported,
+    ) -> MirState<'a, 'tcx> {
+        MirState { mir: None, used, unsupported, state, pretty_types: false }
+    }
 }
 
 /// Trait for converting MIR elements to JSON.
@@ -109,6 +138,19 @@ impl ToJson<'_> for Symbol {
     }
 }
 
+impl ToJson<'_> for Span {
+    fn to_json(&self, mir: &mut MirState) -> serde_json::Value {
+        let loc = mir.state.session.source_map().lookup_char_pos(self.lo());
+        json!({
+            "file": loc.file.name.to_string(),
+            "line": loc.line,
+            // `Loc::col` is zero-based, but lines are one-based.  Report both one-based, to match
+            // rustc's diagnostics.
+            "col": loc.col.0 + 1,
+        })
+    }
+}
+
 impl<'tcx, T> ToJson<'tcx> for Option<T>
 where
     T: ToJson<'tcx>,
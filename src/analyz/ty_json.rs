@@ -1,12 +1,15 @@
 use rustc::hir::{self, Defaultness};
 use rustc::hir::def_id::DefId;
+use rustc::hir::map::DefPathData;
 use rustc::mir;
 use rustc::mir::interpret;
 use rustc::ty;
 use rustc::ty::{TyCtxt, TypeFoldable};
+use rustc::ty::layout::{Size, Variants};
 use rustc::ich::StableHashingContext;
 use rustc_data_structures::indexed_vec::{self, IndexVec};
 use rustc_data_structures::stable_hasher::{HashStable, StableHasher};
+use rustc_target::abi::Endian;
 use rustc_target::spec::abi;
 use syntax::ast;
 use serde_json;
@@ -14,6 +17,23 @@ use std::fmt::Write as FmtWrite;
 use std::usize;
 
 use analyz::to_json::*;
+use analyz::abi_json::{
+    AbiCx, pointee_align_json, uninhabited_json, valid_range_json, variant_layout_json,
+};
+
+/// Pointer casts are emitted as objects so that `Rvalue::Cast` can attach the source and target
+/// types.  `Misc` casts are a plain string, as before.
+impl ToJson<'_> for mir::CastKind {
+    fn to_json(&self, _mir: &mut MirState) -> serde_json::Value {
+        match self {
+            &mir::CastKind::Misc => json!("Misc"),
+            &mir::CastKind::Pointer(ref pc) => json!({
+                "kind": "Pointer",
+                "cast": format!("{:?}", pc),
+            }),
+        }
+    }
+}
 
 impl<'tcx, T> ToJson<'tcx> for ty::List<T>
     where
@@ -34,8 +54,8 @@ basic_json_enum_impl!(ast::UintTy);
 basic_json_enum_impl!(hir::Mutability);
 basic_json_enum_impl!(hir::def::CtorKind);
 basic_json_enum_impl!(mir::Mutability);
-basic_json_enum_impl!(mir::CastKind);
 basic_json_enum_impl!(abi::Abi);
+basic_json_impl!(hir::Unsafety);
 
 impl ToJson<'_> for mir::BorrowKind {
     fn to_json(&self, _mir: &mut MirState) -> serde_json::Value {
@@ -146,6 +166,22 @@ pub fn inst_def_id<'tcx>(
     }
 }
 
+/// The name of the `InstanceDef` variant of `inst`, which is the same as the `kind` of its JSON.
+/// Every kind other than `Item` is a body `rustc` synthesizes (or, for `Intrinsic` and `Virtual`,
+/// has no body at all), rather than one from the source.
+pub fn inst_kind_str(inst: ty::Instance) -> &'static str {
+    match inst.def {
+        ty::InstanceDef::Item(_) => "Item",
+        ty::InstanceDef::Intrinsic(_) => "Intrinsic",
+        ty::InstanceDef::VtableShim(_) => "VtableShim",
+        ty::InstanceDef::FnPtrShim(_, _) => "FnPtrShim",
+        ty::InstanceDef::Virtual(_, _) => "Virtual",
+        ty::InstanceDef::ClosureOnceShim { .. } => "ClosureOnceShim",
+        ty::InstanceDef::DropGlue(_, _) => "DropGlue",
+        ty::InstanceDef::CloneShim(_, _) => "CloneShim",
+    }
+}
+
 impl ToJson<'_> for hir::def_id::DefId {
     fn to_json(&self, mir: &mut MirState) -> serde_json::Value {
         json!(def_id_str(mir.state.tcx, *self))
@@ -216,12 +252,19 @@ impl<'tcx> ToJson<'tcx> for ty::Instance<'tcx> {
                 "call_once": call_once.to_json(mir),
                 "substs": substs.to_json(mir),
             }),
-            ty::InstanceDef::DropGlue(did, ty) => json!({
-                "kind": "DropGlue",
-                "def_id": did.to_json(mir),
-                "substs": substs.to_json(mir),
-                "ty": ty.to_json(mir),
-            }),
+            ty::InstanceDef::DropGlue(did, ty) => {
+                let field_drops = match ty {
+                    Some(ty) => field_drop_glue(mir, did, ty),
+                    None => vec![],
+                };
+                json!({
+                    "kind": "DropGlue",
+                    "def_id": did.to_json(mir),
+                    "substs": substs.to_json(mir),
+                    "ty": ty.to_json(mir),
+                    "field_drops": field_drops,
+                })
+            },
             ty::InstanceDef::CloneShim(did, ty) => {
                 let sub_tys = match ty.sty {
                     ty::TyKind::Array(t, _) => vec![t],
@@ -262,15 +305,65 @@ impl<'tcx> ToJson<'tcx> for ty::Instance<'tcx> {
     }
 }
 
+/// List the drop glue for each field of `ty` that needs dropping, in the order rustc drops them.
+/// `drop_in_place` is the `DefId` of `core::ptr::drop_in_place`.  Fields that don't need dropping
+/// (such as `Copy` types) are omitted.  Enums are not handled, since which fields get dropped
+/// depends on the active variant.
+fn field_drop_glue<'tcx>(
+    mir: &mut MirState<'_, 'tcx>,
+    drop_in_place: DefId,
+    ty: ty::Ty<'tcx>,
+) -> Vec<serde_json::Value> {
+    let tcx = mir.state.tcx;
+    let field_tys = match ty.sty {
+        ty::TyKind::Tuple(substs) => substs.types().collect(),
+        ty::TyKind::Array(t, _) => vec![t],
+        ty::TyKind::Closure(closure_did, substs) =>
+            substs.upvar_tys(closure_did, tcx).collect(),
+        ty::TyKind::Adt(adt_def, _) if adt_def.is_box() => vec![ty.boxed_ty()],
+        ty::TyKind::Adt(adt_def, substs) if adt_def.is_struct() =>
+            adt_def.non_enum_variant().fields.iter()
+                .map(|f| f.ty(tcx, substs))
+                .collect(),
+        _ => vec![],
+    };
+
+    field_tys.into_iter()
+        .filter(|ty| ty.needs_drop(tcx, ty::ParamEnv::reveal_all()))
+        .filter_map(|ty| {
+            let inst = ty::Instance::resolve(
+                tcx,
+                ty::ParamEnv::reveal_all(),
+                drop_in_place,
+                tcx.intern_substs(&[ty.into()]),
+            );
+            let inst = match inst {
+                Some(x) => x,
+                None => {
+                    eprintln!("error: failed to resolve drop glue for field type {:?}", ty);
+                    return None;
+                },
+            };
+            // As with clone shims, the field's own drop glue will list its fields in turn when we
+            // emit it.
+            mir.used.instances.insert(inst);
+            Some(json!({
+                "ty": ty.to_json(mir),
+                "drop": inst_id_str(tcx, inst),
+            }))
+        })
+        .collect()
+}
+
 // For type _references_. To translate ADT defintions, do it explicitly.
 impl<'tcx> ToJson<'tcx> for ty::Ty<'tcx> {
     fn to_json(&self, mir: &mut MirState<'_, 'tcx>) -> serde_json::Value {
-        match &self.sty {
+        let mut j = match &self.sty {
             &ty::TyKind::Bool => {
-                json!({"kind": "Bool"})
+                json!({"kind": "Bool", "valid_range": valid_range_json(mir.state.tcx, self)})
             }
             &ty::TyKind::Char => {
-                json!({"kind": "Char"})
+                json!({"kind": "Char", "valid_range": valid_range_json(mir.state.tcx, self)})
             }
             &ty::TyKind::Int(ref t) => {
                 json!({"kind": "Int", "intkind": t.to_json(mir)})
@@ -291,20 +384,30 @@ impl<'tcx> ToJson<'tcx> for ty::Ty<'tcx> {
                 json!({"kind": "Float", "size": sz.to_json(mir)})
             }
             &ty::TyKind::Array(ref t, ref size) => {
-                json!({"kind": "Array", "ty": t.to_json(mir), "size": size.to_json(mir)})
+                // `size` is the length as a constant, which may be symbolic in generic code.  `len`
+                // is the evaluated length, or `null` if it can't be evaluated.
+                json!({
+                    "kind": "Array",
+                    "ty": t.to_json(mir),
+                    "size": size.to_json(mir),
+                    "len": eval_array_len(mir.state.tcx, size),
+                })
             }
-            &ty::TyKind::Ref(ref _region, ref ty, ref mtbl) => {
+            &ty::TyKind::Ref(region, ref ty, ref mtbl) => {
                 json!({
                     "kind": "Ref",
+                    "region": region.to_json(mir),
                     "ty": ty.to_json(mir),
-                    "mutability": mtbl.to_json(mir)
+                    "mutability": mtbl.to_json(mir),
+                    "pointee_align": pointee_align_json(mir.state.tcx, *ty),
                 })
             }
             &ty::TyKind::RawPtr(ref tm) => {
                 json!({
                     "kind": "RawPtr",
                     "ty": tm.ty.to_json(mir),
-                    "mutability": tm.mutbl.to_json(mir)
+                    "mutability": tm.mutbl.to_json(mir),
+                    "pointee_align": pointee_align_json(mir.state.tcx, tm.ty),
                 })
             }
             &ty::TyKind::Adt(ref adtdef, ref substs) => {
@@ -351,12 +454,36 @@ impl<'tcx> ToJson<'tcx> for ty::Ty<'tcx> {
                     "closuresubsts": closuresubsts.substs.to_json(mir),
                     "upvar_tys": closuresubsts.upvar_tys(defid, mir.state.tcx)
                         .collect::<Vec<_>>().to_json(mir),
+                    "upvar_modes": closure_upvar_modes(mir.state.tcx, defid),
+                    "closure_kind": format!("{:?}",
+                        closuresubsts.closure_kind(defid, mir.state.tcx)),
+                    "call_shims": closure_call_shims(mir, self, defid, closuresubsts),
                 })
             }
             &ty::TyKind::Dynamic(ref preds, _region) => {
+                let preds = preds.skip_binder();
+                // `principal` is `null` for trait objects made only of auto traits, like `dyn
+                // Send`.
+                let principal = preds.principal().map(|tref| json!({
+                    "trait": tref.def_id.to_json(mir),
+                    "substs": tref.substs.to_json(mir),
+                }));
+                let auto_traits = preds.auto_traits()
+                    .map(|did| did.to_json(mir))
+                    .collect::<Vec<_>>();
+                let projections = preds.projection_bounds()
+                    .map(|proj| json!({
+                        "proj": proj.item_def_id.to_json(mir),
+                        "substs": proj.substs.to_json(mir),
+                        "rhs_ty": proj.ty.to_json(mir),
+                    }))
+                    .collect::<Vec<_>>();
                 json!({
                     "kind": "Dynamic",
-                    "predicates": preds.skip_binder().to_json(mir),
+                    "predicates": preds.to_json(mir),
+                    "principal": principal,
+                    "auto_traits": auto_traits,
+                    "projections": projections,
                 })
             }
             &ty::TyKind::Projection(ref pty) => {
@@ -373,11 +500,13 @@ impl<'tcx> ToJson<'tcx> for ty::Ty<'tcx> {
                     "defid": pty.item_def_id.to_json(mir)
                 })
             }
+            // The full signature, including `abi` and `unsafety`, so `unsafe extern "C" fn()` and
+            // `fn()` pointers are distinct types.
             &ty::TyKind::FnPtr(ref sig) => {
                 json!({"kind": "FnPtr", "signature": sig.to_json(mir)})
             }
             &ty::TyKind::Never => {
-                json!({"kind": "Never"})
+                json!({"kind": "Never", "uninhabited": true})
             }
             &ty::TyKind::Error => {
                 json!({"kind": "Error"})
@@ -386,9 +515,17 @@ impl<'tcx> ToJson<'tcx> for ty::Ty<'tcx> {
                 // TODO
                 json!({"kind": "Infer"})
             }
-            &ty::TyKind::Bound(_, _) => {
-                // TODO
-                json!({"kind": "Bound"})
+            &ty::TyKind::Bound(debruijn, ref bound_ty) => {
+                let name = match bound_ty.kind {
+                    ty::BoundTyKind::Anon => None,
+                    ty::BoundTyKind::Param(ref name) => Some(name.to_string()),
+                };
+                json!({
+                    "kind": "Bound",
+                    "debruijn": debruijn.as_u32(),
+                    "var": bound_ty.var.as_u32(),
+                    "name": name,
+                })
             }
             &ty::TyKind::Placeholder(_) => {
                 // TODO
@@ -402,16 +539,119 @@ impl<'tcx> ToJson<'tcx> for ty::Ty<'tcx> {
                 // TODO
                 json!({"kind": "Generator"})
             }
-            &ty::TyKind::GeneratorWitness(_) => {
-                // TODO
-                json!({"kind": "GeneratorWitness"})
+            &ty::TyKind::GeneratorWitness(tys) => {
+                // The witness binds the regions of the types held across yield points.  We emit
+                // the types with their bound regions left in place.
+                json!({
+                    "kind": "GeneratorWitness",
+                    "tys": tys.skip_binder().iter().map(|ty| ty.to_json(mir))
+                        .collect::<Vec<_>>(),
+                })
             }
             &ty::TyKind::Opaque(_, _) => {
                 // TODO
                 json!({"kind": "Opaque"})
             }
+        };
+        if mir.pretty_types {
+            if let Some(obj) = j.as_object_mut() {
+                obj.insert("pretty".to_owned(), self.to_string().into());
+            }
+        }
+        j
+    }
+}
+
+/// Get the capture mode of each upvar of a closure, in the same order as `upvar_tys` (which is
+/// also the order of the closure's fields in MIR).  Returns `null` for non-local closures, since
+/// capture information is only available from the typeck tables of the current crate.
+fn closure_upvar_modes<'tcx>(
+    tcx: TyCtxt<'tcx>,
+    def_id: DefId,
+) -> serde_json::Value {
+    if !def_id.is_local() {
+        return serde_json::Value::Null;
+    }
+    let upvars = match tcx.upvars(def_id) {
+        Some(x) => x,
+        None => return json!([]),
+    };
+    let tables = tcx.typeck_tables_of(def_id);
+    let modes = upvars.keys().map(|&var_id| {
+        let upvar_id = ty::UpvarId {
+            var_path: ty::UpvarPath { hir_id: var_id },
+            closure_expr_id: def_id.to_local(),
+        };
+        match tables.upvar_capture(upvar_id) {
+            ty::UpvarCapture::ByValue => json!("ByValue"),
+            ty::UpvarCapture::ByRef(borrow) => match borrow.kind {
+                ty::BorrowKind::ImmBorrow => json!("ByRef"),
+                ty::BorrowKind::UniqueImmBorrow => json!("ByUniqueRef"),
+                ty::BorrowKind::MutBorrow => json!("ByMutRef"),
+            },
+        }
+    }).collect::<Vec<_>>();
+    json!(modes)
+}
+
+/// Resolve the `call`/`call_mut`/`call_once` method of every `Fn*` trait the closure implements.
+/// An `Fn` closure can be called through all three traits, and each one may go through a
+/// different shim, so we record (and emit) all of them rather than just the narrowest.
+fn closure_call_shims<'tcx>(
+    mir: &mut MirState<'_, 'tcx>,
+    closure_ty: ty::Ty<'tcx>,
+    def_id: DefId,
+    substs: ty::ClosureSubsts<'tcx>,
+) -> serde_json::Value {
+    let tcx = mir.state.tcx;
+    if closure_ty.needs_subst() {
+        return json!([]);
+    }
+
+    let kind = substs.closure_kind(def_id, tcx);
+    let sig = tcx.erase_late_bound_regions(&substs.closure_sig(def_id, tcx));
+    let args_ty = sig.inputs()[0];
+
+    let lang_items = tcx.lang_items();
+    let traits = [
+        (ty::ClosureKind::Fn, lang_items.fn_trait()),
+        (ty::ClosureKind::FnMut, lang_items.fn_mut_trait()),
+        (ty::ClosureKind::FnOnce, lang_items.fn_once_trait()),
+    ];
+
+    let mut shims = Vec::new();
+    for &(trait_kind, trait_did) in traits.iter() {
+        if !kind.extends(trait_kind) {
+            continue;
         }
+        let trait_did = match trait_did {
+            Some(x) => x,
+            None => continue,
+        };
+        let method = match tcx.associated_items(trait_did)
+                .find(|item| item.kind == ty::AssocKind::Method) {
+            Some(x) => x,
+            None => continue,
+        };
+        let call_substs = tcx.mk_substs_trait(closure_ty, &[args_ty.into()]);
+        let inst = ty::Instance::resolve(
+            tcx, ty::ParamEnv::reveal_all(), method.def_id, call_substs);
+        let inst = match inst {
+            Some(x) => x,
+            None => {
+                eprintln!("error: failed to resolve {:?} shim for closure {:?}",
+                    trait_kind, closure_ty);
+                continue;
+            },
+        };
+        mir.used.instances.insert(inst);
+        shims.push(json!({
+            "kind": format!("{:?}", trait_kind),
+            "trait": trait_did.to_json(mir),
+            "inst": inst_id_str(tcx, inst),
+        }));
     }
+    json!(shims)
 }
 
 impl ToJson<'_> for ty::ParamTy {
@@ -420,11 +660,58 @@ impl ToJson<'_> for ty::ParamTy {
     }
 }
 
+impl ToJson<'_> for ty::BoundRegion {
+    fn to_json(&self, _mir: &mut MirState) -> serde_json::Value {
+        match self {
+            &ty::BoundRegion::BrAnon(idx) => json!({"kind": "BrAnon", "index": idx}),
+            &ty::BoundRegion::BrNamed(_, ref name) =>
+                json!({"kind": "BrNamed", "name": name.to_string()}),
+            &ty::BoundRegion::BrFresh(idx) => json!({"kind": "BrFresh", "index": idx}),
+            &ty::BoundRegion::BrEnv => json!({"kind": "BrEnv"}),
+        }
+    }
+}
+
+impl<'tcx> ToJson<'tcx> for ty::Region<'tcx> {
+    fn to_json(&self, mir: &mut MirState<'_, 'tcx>) -> serde_json::Value {
+        match **self {
+            ty::RegionKind::ReEarlyBound(ref ebr) => json!({
+                "kind": "EarlyBound",
+                "index": ebr.index,
+                "name": ebr.name.to_string(),
+            }),
+            // Late-bound regions are identified by the binder they refer to (as a de Bruijn
+            // index, counting outward from the innermost enclosing binder) plus the bound region
+            // itself.  This is enough to tell apart `'a` and `'b` in `for<'a, 'b> fn(&'a u32, &'b
+            // u32)`.
+            ty::RegionKind::ReLateBound(debruijn, ref br) => json!({
+                "kind": "LateBound",
+                "debruijn": debruijn.as_u32(),
+                "bound_region": br.to_json(mir),
+            }),
+            ty::RegionKind::ReStatic => json!({"kind": "Static"}),
+            ty::RegionKind::ReErased => json!({"kind": "Erased"}),
+            ref r => {
+                let mut s = String::new();
+                write!(&mut s, "{:?}", r).unwrap();
+                json!({"kind": "Other", "region": s})
+            },
+        }
+    }
+}
+
 impl<'tcx> ToJson<'tcx> for ty::PolyFnSig<'tcx> {
     fn to_json(&self, ms: &mut MirState<'_, 'tcx>) -> serde_json::Value {
-        // Note: I don't think we need binders in MIR, but we can change
-        // this if we do.
-        self.skip_binder().to_json(ms)
+        // The signature itself is emitted with its binder skipped, so late-bound regions inside
+        // it refer to this binder with de Bruijn index 0.  `bound_vars` lists the regions it
+        // binds, which tells `for<'a> fn(&'a u8)` apart from `fn(&'static u8)`.  In MIR bodies,
+        // `rustc` has already anonymized these, so they show up as `BrAnon`.
+        let mut bound = ms.state.tcx.collect_referenced_late_bound_regions(self)
+            .into_iter().collect::<Vec<_>>();
+        bound.sort();
+        let mut j = self.skip_binder().to_json(ms);
+        j["bound_vars"] = bound.iter().map(|br| br.to_json(ms)).collect::<Vec<_>>().into();
+        j
     }
 }
 
@@ -436,6 +723,8 @@ impl<'tcx> ToJson<'tcx> for ty::FnSig<'tcx> {
             "inputs": input_jsons,
             "output": self.output().to_json(ms),
             "abi": self.abi.to_json(ms),
+            "unsafety": self.unsafety.to_json(ms),
+            "c_variadic": self.c_variadic,
         })
     }
 }
@@ -473,7 +762,30 @@ impl<'tcx> ToJson<'tcx> for ty::Predicate<'tcx> {
                     "trait_proj": ppp.skip_binder().to_json(ms)
                 })
             }
+            // The closure `def_id` implements the call trait for `kind` (and so also the traits
+            // for any kinds that `kind` extends).
+            &ty::Predicate::ClosureKind(def_id, ref substs, kind) => {
+                json!({
+                    "closure_kind": {
+                        "def_id": def_id.to_json(ms),
+                        "substs": substs.substs.to_json(ms),
+                        "kind": format!("{:?}", kind),
+                    }
+                })
+            }
             _ => {
+                let kind = match self {
+                    &ty::Predicate::Trait(..) => "Trait",
+                    &ty::Predicate::RegionOutlives(..) => "RegionOutlives",
+                    &ty::Predicate::TypeOutlives(..) => "TypeOutlives",
+                    &ty::Predicate::Projection(..) => "Projection",
+                    &ty::Predicate::WellFormed(..) => "WellFormed",
+                    &ty::Predicate::ObjectSafe(..) => "ObjectSafe",
+                    &ty::Predicate::ClosureKind(..) => "ClosureKind",
+                    &ty::Predicate::Subtype(..) => "Subtype",
+                    &ty::Predicate::ConstEvaluatable(..) => "ConstEvaluatable",
+                };
+                *ms.unsupported.predicates.entry(kind.to_owned()).or_insert(0) += 1;
                 json!("unknown_pred")
             }
         }
@@ -542,7 +854,15 @@ impl<'tcx> ToJson<'tcx> for ty::GenericPredicates<'tcx> {
 
         let mut json_preds: Vec<serde_json::Value> = Vec::new();
         gather_preds(ms, self, &mut json_preds);
-        json!({ "predicates": json_preds })
+        // `predicates` includes those inherited from the parent (for example, an impl method
+        // inherits the impl's `where` clauses).  `own_predicates` lists only the ones declared on
+        // this item itself, which `gather_preds` puts first.  We reuse their JSON rather than
+        // serializing them again, which would count unsupported predicates twice.
+        let own_preds = json_preds[.. self.predicates.len()].to_vec();
+        json!({
+            "predicates": json_preds,
+            "own_predicates": own_preds,
+        })
     }
 }
 
@@ -577,6 +897,10 @@ impl ToJson<'_> for ty::Generics {
     }
 }
 
+/// Find the trait item that `item` (an item of a trait impl) implements.  Our `rustc` doesn't
+/// record this link directly, so we look it up the same way `rustc` does when checking the impl:
+/// an associated type and a method can share a name, so the item must match in namespace as well
+/// as in name.
 pub fn trait_item_for_impl_item<'tcx>(
     tcx: TyCtxt<'tcx>,
     item: &ty::AssocItem,
@@ -584,11 +908,10 @@ pub fn trait_item_for_impl_item<'tcx>(
     if let ty::AssocItemContainer::ImplContainer(impl_did) = item.container {
         if let Some(trait_ref) = tcx.impl_trait_ref(impl_did) {
             let trait_did = trait_ref.def_id;
-            for trait_item in tcx.associated_items(trait_did) {
-                if trait_item.ident.name == item.ident.name {
-                    return Some(trait_item);
-                }
-            }
+            return tcx.associated_items(trait_did).find(|trait_item| {
+                trait_item.kind.namespace() == item.kind.namespace() &&
+                    tcx.hygienic_eq(item.ident, trait_item.ident, trait_did)
+            });
         }
     }
     None
@@ -604,16 +927,44 @@ pub fn assoc_item_json<'tcx>(
     let did = item.def_id;
     map.insert("name".to_owned(), did.to_json(ms));
     map.insert("generics".to_owned(), tcx.generics_of(did).to_json(ms));
-    map.insert("predicates".to_owned(), tcx.predicates_of(did).to_json(ms));
+    let preds = tcx.predicates_of(did);
+    let preds_json = preds.to_json(ms);
+    map.insert("predicates".to_owned(), preds_json.clone());
 
     match item.kind {
         ty::AssocKind::Const => {
             map.insert("kind".to_owned(), json!("Const"));
             map.insert("type".to_owned(), tcx.type_of(did).to_json(ms));
+            // Evaluate the value if the const (including its impl or trait) has no generics.  We
+            // use the item's own param env, since its body may rely on the impl's `where`
+            // clauses.  Generic consts are evaluated only when they're used, at which point the
+            // `ty::Const` path sees them with concrete substs.
+            let has_value = item.defaultness.has_value();
+            let value = if has_value && tcx.generics_of(did).count() == 0 {
+                let cid = interpret::GlobalId {
+                    instance: ty::Instance::mono(tcx, did),
+                    promoted: None,
+                };
+                tcx.const_eval(tcx.param_env(did).and(cid)).ok()
+            } else {
+                None
+            };
+            map.insert("value".to_owned(), value.to_json(ms));
         }
         ty::AssocKind::Method => {
             map.insert("kind".to_owned(), json!("Method"));
             map.insert("signature".to_owned(), tcx.fn_sig(did).to_json(ms));
+            map.insert("is_const".to_owned(), tcx.is_const_fn(did).into());
+            map.insert("is_const_fn".to_owned(), tcx.is_const_fn(did).into());
+            if let ty::AssocItemContainer::TraitContainer(_) = item.container {
+                // A provided method's body belongs to the trait method itself, so its instances
+                // (for impls that don't override it) are named after `did`.
+                let has_body = item.defaultness.has_value();
+                map.insert("has_default_body".to_owned(), has_body.into());
+                if has_body {
+                    map.insert("default_body".to_owned(), did.to_json(ms));
+                }
+            }
         }
         ty::AssocKind::Type => {
             map.insert("kind".to_owned(), json!("Type"));
@@ -630,6 +981,15 @@ pub fn assoc_item_json<'tcx>(
         }
     }
 
+    if let ty::AssocItemContainer::ImplContainer(_) = item.container {
+        // The impl's own `where` clauses, which decide whether the impl applies at all, as opposed
+        // to any extra bounds on the item.  The impl is the item's parent, and impls have no
+        // parent of their own, so these are exactly the inherited part of `predicates`.
+        let impl_preds = preds_json["predicates"].as_array().unwrap()[preds.predicates.len() ..]
+            .to_vec();
+        map.insert("impl_predicates".to_owned(), impl_preds.into());
+    }
+
     if let Some(trait_item) = trait_item_for_impl_item(tcx, item) {
         map.insert("implements".to_owned(), trait_item.def_id.to_json(ms));
     }
@@ -660,35 +1020,41 @@ impl<'tcx> ToJson<'tcx> for ty::subst::Kind<'tcx> {
     }
 }
 
+/// Evaluate an unevaluated constant.  Returns `None` if the constant can't be resolved to a
+/// specific instance (for example, because `substs` still contains type parameters) or if
+/// evaluation fails.
 fn do_const_eval<'tcx>(
     tcx: TyCtxt<'tcx>,
     def_id: DefId,
     substs: ty::subst::SubstsRef<'tcx>
-) -> &'tcx ty::Const<'tcx> {
+) -> Option<&'tcx ty::Const<'tcx>> {
     let param_env = ty::ParamEnv::reveal_all();
-    let instance = ty::Instance::resolve(tcx, param_env, def_id, substs).unwrap();
+    let instance = ty::Instance::resolve(tcx, param_env, def_id, substs)?;
     let cid = interpret::GlobalId {
         instance,
         promoted: None,
     };
-    tcx.const_eval(param_env.and(cid)).unwrap()
+    tcx.const_eval(param_env.and(cid)).ok()
 }
 
+/// Evaluate an array length.  Returns `None` if the length is symbolic, such as a const generic
+/// parameter or an expression involving one.
 fn eval_array_len<'tcx>(
     tcx: TyCtxt<'tcx>,
     c: &'tcx ty::Const<'tcx>,
-) -> usize {
+) -> Option<usize> {
     let evaluated = match c.val {
-        interpret::ConstValue::Unevaluated(def_id, substs) => {
-            do_const_eval(tcx, def_id, substs)
-        },
+        interpret::ConstValue::Unevaluated(def_id, substs) => do_const_eval(tcx, def_id, substs)?,
         _ => c,
     };
     match evaluated.val {
         interpret::ConstValue::Scalar(interpret::Scalar::Raw { size, data }) => {
             assert!(data <= usize::MAX as u128);
-            data as usize
+            Some(data as usize)
         },
+        interpret::ConstValue::Param(_) |
+        interpret::ConstValue::Infer(_) |
+        interpret::ConstValue::Placeholder(_) => None,
         _ => panic!("impossible: array size is not a scalar?"),
     }
 }
@@ -702,13 +1068,16 @@ fn read_static_memory<'tcx>(
     &alloc.bytes[start .. end]
 }
 
+/// Render the value of a primitive constant, giving the fields to add to its `Const` object.
+/// Integer values (including `bool` and `char`) are decimal strings in `int_val`, and `size` gives
+/// their width in bytes.
 fn render_constant<'tcx>(
     tcx: TyCtxt<'tcx>,
     ty: ty::Ty<'tcx>,
     scalar: Option<(u8, u128)>,
     slice: Option<(&'tcx mir::interpret::Allocation, usize, usize)>,
-) -> Option<(&'static str, serde_json::Value)> {
-    Some(match ty.sty {
+) -> Option<serde_json::Map<String, serde_json::Value>> {
+    let (key, val) = match ty.sty {
         ty::TyKind::Int(_) => {
             let (size, bits) = scalar.expect("int const had non-scalar value?");
             let mut val = bits as i128;
@@ -753,15 +1122,137 @@ fn render_constant<'tcx>(
             }, len_const),
             ..
         }, hir::Mutability::MutImmutable) => {
-            let len = eval_array_len(tcx, len_const);
+            let len = eval_array_len(tcx, len_const)
+                .expect("bytestring literal has symbolic length?");
             let (alloc, start, _) = slice.expect("string const had non-slice value");
             let end = start + len;
             let mem = read_static_memory(alloc, start, end);
             ("bstr_val", mem.into())
         },
 
+        // C-like enums are represented by their tag alone.  The tag may be narrower than the
+        // discriminant type (`rustc` picks the smallest integer that fits), so we compare the
+        // discriminants truncated to the tag's size.
+        ty::TyKind::Adt(adt_def, _) if is_fieldless_enum(adt_def) => {
+            let (size, bits) = scalar.expect("enum const had non-scalar value?");
+            let size = Size::from_bytes(size as u64);
+            let variant = if adt_def.variants.len() == 1 {
+                Some(0)
+            } else {
+                adt_def.discriminants(tcx)
+                    .find(|&(_, discr)| size.truncate(discr.val) == bits)
+                    .map(|(idx, _)| idx.as_usize())
+            };
+            ("enum_val", json!({"variant": variant?}))
+        },
+
         _ => return None,
-    })
+    };
+    let mut fields = value_fields(key, val);
+    if key == "int_val" {
+        let (size, _) = scalar.unwrap();
+        fields.insert("size".to_owned(), size.into());
+    }
+    Some(fields)
+}
+
+fn is_fieldless_enum(adt_def: &ty::AdtDef) -> bool {
+    adt_def.is_enum() && adt_def.variants.iter().all(|v| v.fields.is_empty())
+}
+
+fn value_fields(key: &str, val: serde_json::Value) -> serde_json::Map<String, serde_json::Value> {
+    let mut fields = serde_json::Map::new();
+    fields.insert(key.to_owned(), val);
+    fields
+}
+
+/// Read the `size`-byte integer at `start` in `alloc`, in the target's byte order.  Returns `None`
+/// if any of those bytes are part of a pointer.
+fn read_scalar_bits<'tcx>(
+    tcx: TyCtxt<'tcx>,
+    alloc: &'tcx interpret::Allocation,
+    start: usize,
+    size: usize,
+) -> Option<u128> {
+    let end = start + size;
+    let ptr_size = tcx.data_layout.pointer_size.bytes() as usize;
+    let overlaps_reloc = alloc.relocations.iter().any(|&(off, _)| {
+        let off = off.bytes() as usize;
+        off < end && off + ptr_size > start
+    });
+    if overlaps_reloc {
+        return None;
+    }
+    let bytes = &alloc.bytes[start .. end];
+    let mut bits = 0_u128;
+    match tcx.data_layout.endian {
+        Endian::Little => for &b in bytes.iter().rev() { bits = bits << 8 | b as u128 },
+        Endian::Big => for &b in bytes { bits = bits << 8 | b as u128 },
+    }
+    Some(bits)
+}
+
+/// Render a constant stored in memory at `offset` in `alloc`, which is how `ConstValue::ByRef`
+/// represents large and aggregate constants.  Primitive values are read from the allocation and
+/// rendered by `render_constant`.  Arrays, tuples, and single-variant ADTs become a list of their
+/// elements or fields (in declaration order), each a `Const` object of its own.  Returns `None` if
+/// any part can't be rendered, such as a pointer or an enum with fields.
+fn render_by_ref<'tcx>(
+    mir: &mut MirState<'_, 'tcx>,
+    ty: ty::Ty<'tcx>,
+    alloc: &'tcx interpret::Allocation,
+    offset: Size,
+) -> Option<serde_json::Map<String, serde_json::Value>> {
+    let tcx = mir.state.tcx;
+    let cx = AbiCx { tcx };
+    let layout = tcx.layout_of(ty::ParamEnv::reveal_all().and(ty)).ok()?;
+
+    let mut fields = || -> Option<Vec<serde_json::Value>> {
+        let mut v = Vec::with_capacity(layout.fields.count());
+        for i in 0 .. layout.fields.count() {
+            let field_ty = layout.field(&cx, i).ty;
+            let field_offset = offset + layout.fields.offset(i);
+            let rendered = render_by_ref(mir, field_ty, alloc, field_offset)?;
+            let mut j = serde_json::Map::new();
+            j.insert("kind".to_owned(), "Const".into());
+            j.insert("ty".to_owned(), field_ty.to_json(mir));
+            j.extend(rendered);
+            v.push(j.into());
+        }
+        Some(v)
+    };
+
+    // Values that `render_constant` can handle directly, given their bits.
+    let is_scalar = match ty.sty {
+        ty::TyKind::Int(_) |
+        ty::TyKind::Uint(_) |
+        ty::TyKind::Bool |
+        ty::TyKind::Char |
+        ty::TyKind::Float(_) => true,
+        ty::TyKind::Adt(adt_def, _) => is_fieldless_enum(adt_def),
+        _ => false,
+    };
+    if is_scalar {
+        let size = layout.size.bytes() as usize;
+        let bits = read_scalar_bits(tcx, alloc, offset.bytes() as usize, size)?;
+        return render_constant(tcx, ty, Some((size as u8, bits)), None);
+    }
+
+    match ty.sty {
+        ty::TyKind::Array(..) => Some(value_fields("array_val", fields()?.into())),
+        ty::TyKind::Tuple(..) => Some(value_fields("tuple_val", fields()?.into())),
+        ty::TyKind::Adt(adt_def, _) if !adt_def.is_union() => {
+            let index = match layout.variants {
+                Variants::Single { index } => index,
+                Variants::Multiple { .. } => return None,
+            };
+            Some(value_fields("adt_val", json!({
+                "variant": index.as_usize(),
+                "fields": fields()?,
+            })))
+        },
+        _ => None,
+    }
 }
 
 impl<'tcx> ToJson<'tcx> for ty::Const<'tcx> {
@@ -778,9 +1269,22 @@ impl<'tcx> ToJson<'tcx> for ty::Const<'tcx> {
                 } else {
                     eprintln!("error: failed to resolve constant {:?}, {:?}", def_id, substs);
                 }
+                // Anonymous consts (such as array lengths and enum discriminants) have no named
+                // item of their own.  Inline `const { .. }` blocks would also fall in this
+                // category, but they aren't supported by our current `rustc`.
+                let anonymous = mir.state.tcx.def_key(def_id).disambiguated_data.data ==
+                    DefPathData::AnonConst;
                 map.insert("initializer".to_owned(), json!({
                     "def_id": def_id.to_json(mir),
                     "substs": substs.to_json(mir),
+                    "anonymous": anonymous,
+                }));
+            },
+            // A const generic parameter, as in `[T; N]`.
+            interpret::ConstValue::Param(p) => {
+                map.insert("param".to_owned(), json!({
+                    "index": p.index,
+                    "name": p.name.to_string(),
                 }));
             },
             _ => {},
@@ -788,7 +1292,14 @@ impl<'tcx> ToJson<'tcx> for ty::Const<'tcx> {
 
         let evaluated = match self.val {
             interpret::ConstValue::Unevaluated(def_id, substs) => {
-                do_const_eval(mir.state.tcx, def_id, substs)
+                match do_const_eval(mir.state.tcx, def_id, substs) {
+                    Some(x) => x,
+                    None => {
+                        // Leave the value out, so consumers fall back on the `initializer`.
+                        map.insert("unresolved".to_owned(), true.into());
+                        return map.into();
+                    },
+                }
             },
             _ => self,
         };
@@ -805,10 +1316,13 @@ impl<'tcx> ToJson<'tcx> for ty::Const<'tcx> {
             interpret::ConstValue::Slice { data, start, end } => {
                 render_constant(mir.state.tcx, self.ty, None, Some((data, start, end)))
             },
+            interpret::ConstValue::ByRef { alloc, offset } => {
+                render_by_ref(mir, self.ty, alloc, offset)
+            },
             _ => None,
         };
-        if let Some((key, val)) = rendered {
-            map.insert(key.to_owned(), val);
+        if let Some(fields) = rendered {
+            map.extend(fields);
         }
 
         map.into()
@@ -881,11 +1395,44 @@ impl ToJsonAg for ty::AdtDef {
         mir: &mut MirState<'_, 'tcx>,
         substs: ty::subst::SubstsRef<'tcx>,
     ) -> serde_json::Value {
-        json!({
+        let mut variants = self.variants.tojson(mir, substs);
+        // Layout is only included in the top-level ADT definition (which has empty `substs`), not
+        // in every aggregate rvalue.  Generic ADTs have no layout, so they get no layout info.
+        let mut valid_range = None;
+        let mut uninhabited = None;
+        let mut variances = None;
+        if substs.is_empty() {
+            let ty = mir.state.tcx.type_of(self.did);
+            for (idx, v) in self.variants.indices().zip(variants.as_array_mut().unwrap()) {
+                if let Some(layout) = variant_layout_json(mir.state.tcx, ty, idx) {
+                    v["memory_order"] = layout["memory_order"].clone();
+                    v["field_offsets"] = layout["field_offsets"].clone();
+                }
+            }
+            // Set for scalar types with a restricted range, like `NonZeroU32`, and for fieldless
+            // enums, whose range covers their discriminants.
+            valid_range = valid_range_json(mir.state.tcx, ty);
+            uninhabited = uninhabited_json(mir.state.tcx, ty);
+            // One entry per generic parameter (lifetimes included), in the order of `generics`.
+            variances = Some(mir.state.tcx.variances_of(self.did).iter()
+                .map(|v| format!("{:?}", v))
+                .collect::<Vec<_>>());
+        }
+        let mut j = json!({
             "name": self.did.to_json(mir),
             "kind": format!("{:?}", self.adt_kind()),
-            "variants": self.variants.tojson(mir, substs)
-        })
+            "variants": variants
+        });
+        if let Some(r) = valid_range {
+            j["valid_range"] = r;
+        }
+        if let Some(u) = uninhabited {
+            j["uninhabited"] = u.into();
+        }
+        if let Some(v) = variances {
+            j["variances"] = v.into();
+        }
+        j
     }
 }
 
@@ -1,3 +1,7 @@
+//! `cargo crux-test`: run a crate's tests under `crux-mir`.  This runs `cargo test` with
+//! `RUSTC_WRAPPER` pointing at `mir-json-rustc-wrapper`, which exports MIR for every crate and
+//! replaces each test binary with a script that runs `crux-mir` on the linked MIR.  Cargo then
+//! runs those scripts as usual.  Extra arguments are passed through to `cargo test`.
 #![feature(rustc_private)]
 extern crate rustc;
 
@@ -6,35 +10,73 @@ use std::path::PathBuf;
 use std::process::{self, Command};
 use rustc::session::config::host_triple;
 
+const CARGO_CRUX_TEST_HELP: &str = r#"Runs a crate's tests using crux-mir
+
+Usage:
+    cargo crux-test [options] [<test-args>...]
+
+Options are the same as `cargo test`.
+
+Environment variables:
+    CRUX_RUST_LIBRARY_PATH   directory containing MIR for the standard libraries
+    CRUX_MIR                 path to the crux-mir binary (default: crux-mir)
+    CRUX_MIR_ARGS            extra arguments to pass to crux-mir
+    MIR_JSON_PRUNE           drop unreachable items when linking (default: set; `0` disables)
+"#;
+
 fn main() {
-    let cargo = env::var("CARGO").unwrap();
+    // When run as `cargo crux-test`, the first arg is this binary's name, and the second is the
+    // cargo subcommand `crux-test` that caused this binary to be invoked.  When run directly as
+    // `cargo-crux-test`, the subcommand is missing.
+    let skip = if env::args().nth(1).map_or(false, |s| s == "crux-test") { 2 } else { 1 };
+    let orig_args = env::args().skip(skip).collect::<Vec<_>>();
 
-    // First arg is this binary's name, second arg is the cargo argument `crux-test` that caused
-    // this binary to be invoked.
-    let orig_args = env::args().skip(2).collect::<Vec<_>>();
+    if orig_args.iter().take_while(|s| *s != "--").any(|s| s == "--help" || s == "-h") {
+        println!("{}", CARGO_CRUX_TEST_HELP);
+        return;
+    }
+
+    if env::var_os("CRUX_RUST_LIBRARY_PATH").is_none() {
+        eprintln!("warning: CRUX_RUST_LIBRARY_PATH is not set, so the standard libraries' MIR \
+            won't be found unless it's on the library search path");
+    }
+
+    let cargo = env::var("CARGO").unwrap_or_else(|_| "cargo".into());
 
     let mut args = Vec::new();
     args.push("test".into());
     // XXX big hack.  See `mir-json-rustc-wrapper.rs` for an explanation of why we set `--target`
-    // explicitly to its default value.
-    args.push("--target".into());
-    // TODO: autodetect the default target.
-    args.push(host_triple().into());
+    // explicitly to its default value.  If the user picked a target, we use theirs instead.
+    let has_target = orig_args.iter().take_while(|s| *s != "--")
+        .any(|s| s == "--target" || s.starts_with("--target="));
+    if !has_target {
+        args.push("--target".into());
+        args.push(host_triple().into());
+    }
     args.extend(orig_args.into_iter());
 
+    let wrapper_name = format!("mir-json-rustc-wrapper{}", env::consts::EXE_SUFFIX);
     let my_path = PathBuf::from(env::args_os().nth(0).unwrap());
     let wrapper_path = if let Some(dir) = my_path.parent() {
-        dir.join("mir-json-rustc-wrapper")
+        dir.join(&wrapper_name)
     } else {
-        PathBuf::from("mir-json-rustc-wrapper")
+        PathBuf::from(&wrapper_name)
     };
 
-    let status = Command::new(&cargo)
-        .args(&args)
-        .env("RUSTC_WRAPPER", wrapper_path)
-        .status().unwrap();
+    let mut cmd = Command::new(&cargo);
+    cmd.args(&args).env("RUSTC_WRAPPER", wrapper_path);
+    // Without pruning, the linked output for each test would include all of `std`.
+    if env::var_os("MIR_JSON_PRUNE").is_none() {
+        cmd.env("MIR_JSON_PRUNE", "1");
+    }
+    let status = match cmd.status() {
+        Ok(x) => x,
+        Err(e) => {
+            eprintln!("error: failed to run {}: {}", cargo, e);
+            process::exit(1);
+        },
+    };
     // `code` can return `None` if the process was terminated by a signal.  We return nonzero
     // ourselves in that case.
     process::exit(status.code().unwrap_or(1));
 }
-
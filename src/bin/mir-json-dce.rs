@@ -42,6 +42,7 @@ fn main() {
     let mut inputs = env::args().skip(1).map(|arg| File::open(&arg))
         .collect::<io::Result<Vec<_>>>().unwrap();
     let mut output = io::BufWriter::new(io::stdout());
-    let j = link::link_crates(&mut inputs, output).unwrap();
+    let opts = link::LinkOptions { prune: true, ..link::LinkOptions::default() };
+    let j = link::link_crates_with(&mut inputs, output, opts).unwrap();
     debug!("{:?}: link crates", measure());
 }
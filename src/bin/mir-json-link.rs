@@ -0,0 +1,71 @@
+//! Link existing `.mir` files into a single JSON file, without rerunning the compiler.  This does
+//! the same linking step as `mir-json-rustc-wrapper`, and is handy for relinking after editing
+//! or rebuilding one of the inputs.
+//!
+//! Usage: `mir-json-link <main.mir> <extern.mir>... -o <out.json> [--prune] [--test-script <path>]`
+//!
+//! The first input is the main crate.  With `--prune`, drop items that aren't reachable from the
+//! tests or the entry point (also enabled by `MIR_JSON_PRUNE`).  With `--test-script`, also write
+//! a script that runs `crux-mir` on the output, like the one the wrapper writes in place of a test
+//! executable.
+extern crate mir_json;
+
+use std::env;
+use std::path::PathBuf;
+use std::process;
+use mir_json::link;
+use mir_json::test_script::write_test_script;
+
+const USAGE: &str = "usage: mir-json-link <main.mir> <extern.mir>... -o <out.json> [--prune] \
+    [--test-script <path>]";
+
+fn usage_error(msg: &str) -> ! {
+    eprintln!("error: {}", msg);
+    eprintln!("{}", USAGE);
+    process::exit(2);
+}
+
+fn main() {
+    let mut inputs = Vec::new();
+    let mut out_path = None;
+    let mut script_path = None;
+    let mut opts = link::LinkOptions::from_env();
+
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match &arg as &str {
+            "-o" => match args.next() {
+                Some(p) => out_path = Some(PathBuf::from(p)),
+                None => usage_error("-o requires an argument"),
+            },
+            "--test-script" => match args.next() {
+                Some(p) => script_path = Some(PathBuf::from(p)),
+                None => usage_error("--test-script requires an argument"),
+            },
+            "--prune" => opts.prune = true,
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                return;
+            },
+            _ if arg.starts_with('-') => usage_error(&format!("unknown option {}", arg)),
+            _ => inputs.push(PathBuf::from(arg)),
+        }
+    }
+
+    let out_path = out_path.unwrap_or_else(|| usage_error("no output file given"));
+    if inputs.is_empty() {
+        usage_error("no input files given");
+    }
+
+    if let Err(e) = link::link_mir_files(&inputs, &out_path, opts) {
+        eprintln!("error: {}", e);
+        process::exit(1);
+    }
+
+    if let Some(script_path) = script_path {
+        if let Err(e) = write_test_script(&script_path, &out_path) {
+            eprintln!("error: failed to write {}: {}", script_path.display(), e);
+            process::exit(1);
+        }
+    }
+}
@@ -16,7 +16,8 @@ extern crate rustc_target;
 extern crate mir_json;
 
 use mir_json::analyz;
-use mir_json::link;
+use mir_json::wrapper::{self, arg_value, is_host_build};
+use rustc::hir::def_id::LOCAL_CRATE;
 use rustc::session::Session;
 use rustc_driver::{Callbacks, Compilation};
 use rustc_interface::interface::{Compiler, Config};
@@ -25,12 +26,12 @@ use rustc_codegen_utils::codegen_backend::CodegenBackend;
 use rustc_metadata::cstore::CStore;
 use rustc_target::spec::PanicStrategy;
 use syntax::ast;
+use syntax::source_map::FileName;
 use std::env;
 use std::error::Error;
-use std::fs::{File, OpenOptions};
-use std::io::{self, Write};
-use std::iter;
-use std::os::unix::fs::OpenOptionsExt;
+use std::fs;
+use std::io;
+#[cfg(unix)]
 use std::os::unix::process::CommandExt;
 use std::path::{Path, PathBuf};
 use std::process::Command;
@@ -76,53 +77,177 @@ fn get_output_path(args: &[String]) -> PathBuf {
 #[derive(Debug, Default)]
 struct MirJsonCallbacks {
     analysis_data: Option<analyz::AnalysisData<()>>,
+    /// If set, look up the `.mir` output in the cache before exporting, and store it there
+    /// afterward.  These are the compiler arguments, which are part of the cache key.
+    cache_args: Option<Vec<String>>,
 }
 
 impl rustc_driver::Callbacks for MirJsonCallbacks {
     /// Called after analysis. Return value instructs the compiler whether to
     /// continue the compilation afterwards (defaults to `Compilation::Continue`)
     fn after_analysis(&mut self, compiler: &Compiler) -> Compilation {
-        self.analysis_data = analyz::analyze(compiler).unwrap();
+        // The key is computed here, rather than up front, because only now do we know every file
+        // the compiler read.
+        let cache = match (&self.cache_args, mir_output_path(compiler)) {
+            (&Some(ref args), Some(mir_path)) => match cache_key(compiler, args) {
+                Ok(key) => Some((wrapper::cache_dir(&mir_path), key, mir_path)),
+                Err(e) => {
+                    eprintln!("warning: failed to compute MIR cache key: {}", e);
+                    None
+                },
+            },
+            _ => None,
+        };
+
+        if let Some((ref dir, ref key, ref mir_path)) = cache {
+            match wrapper::restore_cached_mir(dir, key, mir_path) {
+                Ok(true) => {
+                    eprintln!("using cached MIR {}", dir.join(key).display());
+                    return Compilation::Continue;
+                },
+                Ok(false) => {},
+                Err(e) => eprintln!("warning: failed to read cached MIR: {}", e),
+            }
+        }
+
+        self.analysis_data = match analyz::analyze(compiler) {
+            Ok(x) => x,
+            Err(e) => {
+                eprintln!("error: {}", e);
+                std::process::exit(1);
+            },
+        };
+
+        if let Some((ref dir, ref key, ref mir_path)) = cache {
+            if mir_path.exists() {
+                if let Err(e) = wrapper::store_cached_mir(dir, key, mir_path) {
+                    eprintln!("warning: failed to cache MIR: {}", e);
+                }
+            }
+        }
         Compilation::Continue
     }
 }
 
-fn link_mirs(main_path: PathBuf, extern_paths: &[PathBuf], out_path: &Path) {
-    let mut inputs = iter::once(&main_path).chain(extern_paths.iter())
-        .map(File::open)
-        .collect::<io::Result<Vec<_>>>().unwrap();
-    let mut output = io::BufWriter::new(File::create(out_path).unwrap());
-    link::link_crates(&mut inputs, output).unwrap();
+/// Get the path of the `.mir` file that `analyz::analyze` would write, or `None` if it wouldn't
+/// write one.
+fn mir_output_path(compiler: &Compiler) -> Option<PathBuf> {
+    let sess = compiler.session();
+    let outputs = compiler.prepare_outputs().unwrap().peek();
+    if !outputs.outputs.contains_key(&config::OutputType::Exe) {
+        return None;
+    }
+    let crate_name = compiler.crate_name().unwrap().peek();
+    Some(rustc_codegen_utils::link::out_filename(
+        sess,
+        sess.crate_types.get().first().unwrap().clone(),
+        &outputs,
+        &crate_name,
+    ).with_extension("mir"))
 }
 
-fn write_test_script(script_path: &Path, json_path: &Path) -> io::Result<()> {
-    let json_name = json_path.file_name().unwrap().to_str().unwrap();
-    let mut f = OpenOptions::new().write(true).create(true).truncate(true)
-        .mode(0o755).open(script_path)?;
-    writeln!(f, "#!/bin/sh")?;
-    writeln!(f, r#"exec crux-mir --assert-false-on-error "$(dirname "$0")"/'{}'"#, json_name)?;
-    Ok(())
+/// Compute the MIR cache key for the crate `compiler` has just analyzed.  See
+/// `wrapper::mir_cache_key` for what the key covers.
+fn cache_key(compiler: &Compiler, args: &[String]) -> io::Result<String> {
+    let mut files = Vec::new();
+    // Every source file the compiler read, including modules outside the crate root's directory
+    // and files loaded by `include!` or `include_str!`.  Imported files come from dependencies,
+    // which are covered below.
+    for sf in compiler.session().source_map().files().iter() {
+        if let FileName::Real(ref path) = sf.name {
+            if !sf.is_imported() {
+                files.push(path.clone());
+            }
+        }
+    }
+    // Each dependency's metadata, along with the MIR exported for it.
+    let mut gcx = compiler.global_ctxt().unwrap().peek_mut();
+    gcx.enter(|tcx| {
+        for &cnum in tcx.all_crate_nums(LOCAL_CRATE) {
+            let src = tcx.used_crate_source(cnum);
+            let it = src.dylib.iter()
+                .chain(src.rlib.iter())
+                .chain(src.rmeta.iter());
+            for &(ref path, _) in it {
+                files.push(path.clone());
+                files.push(path.with_extension("mir"));
+            }
+        }
+    });
+    wrapper::mir_cache_key(args, env::vars_os(), &files, &env::current_exe()?)
+}
+
+/// Find the `.mir` file for library crate `name` (such as `core`) in `dir`.  Library files are
+/// named like `libcore-<hash>.mir`.
+fn find_library_mir(dir: &Path, name: &str) -> io::Result<Option<PathBuf>> {
+    let prefix = format!("lib{}-", name);
+    let exact = format!("lib{}.mir", name);
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        let file_name = match path.file_name().and_then(|s| s.to_str()) {
+            Some(x) => x,
+            None => continue,
+        };
+        if path.extension().map_or(false, |ext| ext == "mir") &&
+                (file_name.starts_with(&prefix) || file_name == exact) {
+            return Ok(Some(path));
+        }
+    }
+    Ok(None)
+}
+
+/// For `#![no_std]` crates, `core` and `alloc` may not show up among the crate's dependencies, so
+/// add their MIR from `CRUX_RUST_LIBRARY_PATH` if it isn't already present.
+fn add_no_std_libraries(extern_paths: &mut Vec<PathBuf>) {
+    let lib_dir = match env::var("CRUX_RUST_LIBRARY_PATH") {
+        Ok(x) => PathBuf::from(x),
+        Err(_) => {
+            eprintln!("warning: CRUX_RUST_LIBRARY_PATH is not set; can't add no_std libraries");
+            return;
+        },
+    };
+    for &name in ["alloc", "core"].iter() {
+        let prefix = format!("lib{}-", name);
+        let present = extern_paths.iter().any(|p| {
+            p.file_name().and_then(|s| s.to_str()).map_or(false, |s| s.starts_with(&prefix))
+        });
+        if present {
+            continue;
+        }
+        match find_library_mir(&lib_dir, name) {
+            Ok(Some(path)) => extern_paths.insert(0, path),
+            Ok(None) => eprintln!("warning: no MIR for {} found in {}", name, lib_dir.display()),
+            Err(e) => eprintln!("warning: failed to search {}: {}", lib_dir.display(), e),
+        }
+    }
+}
+
+/// Replace the current process with `cmd`.
+#[cfg(unix)]
+fn exec_command(cmd: &mut Command) -> ! {
+    let e = cmd.exec();
+    unreachable!("exec failed: {:?}", e);
+}
+
+/// Windows has no `exec`, so run `cmd` as a child process and exit with its status.
+#[cfg(not(unix))]
+fn exec_command(cmd: &mut Command) -> ! {
+    let status = cmd.status()
+        .unwrap_or_else(|e| panic!("failed to run {:?}: {:?}", cmd, e));
+    std::process::exit(status.code().unwrap_or(1));
 }
 
 fn go() {
     // First arg is the name of the `rustc` binary that cargo means to invoke, which we ignore.
     let mut args: Vec<String> = std::env::args().skip(1).collect();
 
-    // XXX big hack: We need to use normal rustc (with its normal libs) for `build.rs` scripts,
-    // since our custom libs aren't actually functional.  To distinguish `build.rs` and `build.rs`
-    // dependencies from other compilation jobs, we pass `--target x86_64-unknown-linux-gnu` to
-    // `cargo`.  This makes cargo use cross-compilation mode, even though the host and target
-    // triples are the same.  In that mode, it passes the provided `--target` through to target
-    // jobs, and omit `--target` for host jobs.  So if `--target` is missing, this is a `build.rs`
-    // build, and we should `exec` the real Rust compiler instead of doing our normal thing.
-    if args.iter().position(|s| s == "--target").is_none() {
+    // We need to use normal rustc (with its normal libs) for `build.rs` scripts, since our custom
+    // libs aren't actually functional.  See `is_host_build` for how we detect these.
+    if is_host_build(&args, env::var_os("CARGO_PRIMARY_PACKAGE").is_some()) {
         let rustc = &args[0];
         let args = &args[1..];
         eprintln!("this is a host build - exec {:?} {:?}", rustc, args);
-        let e = Command::new(rustc)
-            .args(args)
-            .exec();
-        unreachable!("exec failed: {:?}", e);
+        exec_command(Command::new(rustc).args(args));
     }
 
     // All build steps need `--cfg crux` and library paths.
@@ -133,16 +258,26 @@ fn go() {
         args.push("-L".into());
         args.push(s);
     }
+    // This comes before computing the cache key, so each level is cached separately.
+    args.extend(analyz::mir_opt_level_args());
 
 
     let test_idx = match args.iter().position(|s| s == "--test") {
         None => {
             eprintln!("normal build - {:?}", args);
             // This is a normal, non-test build.  Just run the build, generating a `.mir` file
-            // alongside the normal output.
+            // alongside the normal output.  The `.mir` file may come from the cache instead, if
+            // nothing has changed since the last time we exported this crate.
+            // The cache only holds `.mir` files, so split output is never cached.
+            let cache_args = if env::var("MIR_JSON_NO_CACHE").is_ok() ||
+                    env::var("MIR_JSON_SPLIT").is_ok() {
+                None
+            } else {
+                Some(args.clone())
+            };
             rustc_driver::run_compiler(
                 &args,
-                &mut MirJsonCallbacks::default(),
+                &mut MirJsonCallbacks { cache_args, ..MirJsonCallbacks::default() },
                 None,
                 None,
             ).unwrap();
@@ -181,20 +316,27 @@ fn go() {
         None,
         None,
     ).unwrap();
-    let data = callbacks.analysis_data
-        .expect("failed to find main MIR path");
-
-    let json_path = test_path.with_extension(".linked-mir.json");
-    eprintln!("linking {} mir files into {}", 1 + data.extern_mir_paths.len(), json_path.display());
-    eprintln!(
-        "  inputs: {}{}",
-        data.mir_path.display(),
-        data.extern_mir_paths.iter().map(|x| format!(" {}", x.display())).collect::<String>(),
-    );
-    link_mirs(data.mir_path, &data.extern_mir_paths, &json_path);
-
-    write_test_script(&test_path, &json_path).unwrap();
-    eprintln!("generated test script {}", test_path.display());
+    let mut data = match callbacks.analysis_data {
+        Some(x) => x,
+        None => {
+            // `analyze` only skips the export when there's no `Exe` output to put the `.mir` file
+            // next to.
+            eprintln!("error: no MIR was exported for the test build of {}, because it doesn't \
+                produce an executable; check that `--emit` includes `link`",
+                arg_value(&args, "--crate-name").unwrap_or("<unknown crate>"));
+            std::process::exit(1);
+        },
+    };
+    if data.no_std {
+        add_no_std_libraries(&mut data.extern_mir_paths);
+    }
+
+    let opts = wrapper::TestBuildOptions::from_env();
+    if let Err(e) = wrapper::link_test_build(
+            &data.mir_path, &data.extern_mir_paths, &test_path, opts) {
+        eprintln!("error: {}", e);
+        std::process::exit(1);
+    }
 }
 
 fn main() {
@@ -13,6 +13,7 @@ extern crate rustc_target;
 extern crate mir_json;
 
 use mir_json::analyz;
+use mir_json::lib_util;
 use rustc::session::Session;
 use rustc_driver::{Callbacks, Compilation};
 use rustc_interface::interface::{Compiler, Config};
@@ -21,18 +22,58 @@ use rustc_codegen_utils::codegen_backend::CodegenBackend;
 use rustc_metadata::cstore::CStore;
 use rustc_target::spec::PanicStrategy;
 use syntax::ast;
+use std::env;
 use std::error::Error;
 use std::fs::File;
-use std::io::Write;
+use std::io::{self, Write};
 use std::path::Path;
 
-struct MirJsonCallbacks;
+struct MirJsonCallbacks {
+    /// Write the JSON to stdout instead of to a `.mir` file.
+    emit_stdout: bool,
+    /// Print the crate's test functions as JSON, and stop without exporting anything.
+    list_tests: bool,
+    /// Print the MIR of just this function (and the ADTs it uses) as JSON, and stop without
+    /// exporting anything else.
+    export_item: Option<String>,
+}
 
 impl rustc_driver::Callbacks for MirJsonCallbacks {
     /// Called after analysis. Return value instructs the compiler whether to
     /// continue the compilation afterwards (defaults to `Compilation::Continue`)
     fn after_analysis(&mut self, compiler: &Compiler) -> Compilation {
-        analyz::analyze(compiler).unwrap();
+        if self.list_tests {
+            println!("{}", analyz::list_tests(compiler));
+            return Compilation::Stop;
+        }
+        if let Some(ref path) = self.export_item {
+            match analyz::export_item(compiler, path) {
+                Ok(Some(j)) => {
+                    let stdout = io::stdout();
+                    let pretty = lib_util::pretty_json_enabled();
+                    lib_util::write_json(stdout.lock(), &j, pretty).unwrap();
+                    println!();
+                },
+                Ok(None) => {
+                    eprintln!("error: no non-generic function named {} in this crate", path);
+                    std::process::exit(1);
+                },
+                Err(e) => {
+                    eprintln!("error: {}", e);
+                    std::process::exit(1);
+                },
+            }
+            return Compilation::Stop;
+        }
+        let result = if self.emit_stdout {
+            analyz::analyze_to_stdout(compiler)
+        } else {
+            analyz::analyze(compiler)
+        };
+        if let Err(e) = result {
+            eprintln!("error: {}", e);
+            std::process::exit(1);
+        }
         Compilation::Continue
     }
 }
@@ -70,9 +111,31 @@ fn go() {
     }
     */
 
+    // `--emit-stdout` is our own flag, so remove it before passing the args on to rustc.
+    let mut emit_stdout = env::var("MIR_JSON_OUT").map_or(false, |s| s == "-");
+    if let Some(idx) = args.iter().position(|s| s == "--emit-stdout") {
+        args.remove(idx);
+        emit_stdout = true;
+    }
+    let mut list_tests = false;
+    if let Some(idx) = args.iter().position(|s| s == "--list-tests") {
+        args.remove(idx);
+        list_tests = true;
+    }
+    let mut export_item = env::var("MIR_JSON_EXPORT_ITEM").ok();
+    if let Some(idx) = args.iter().position(|s| s == "--export-item") {
+        if idx + 1 >= args.len() {
+            eprintln!("error: --export-item requires an argument");
+            std::process::exit(2);
+        }
+        export_item = Some(args.remove(idx + 1));
+        args.remove(idx);
+    }
+    args.extend(analyz::mir_opt_level_args());
+
     rustc_driver::run_compiler(
         &args, // args: &[String]
-        &mut MirJsonCallbacks,
+        &mut MirJsonCallbacks { emit_stdout, list_tests, export_item },
         None,
         None,
     ).unwrap();
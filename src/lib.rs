@@ -5,6 +5,10 @@ extern crate serde;
 extern crate serde_cbor;
 #[macro_use] extern crate serde_derive;
 extern crate tar;
+extern crate rayon;
+extern crate sha2;
+extern crate flate2;
+extern crate shell_words;
 
 extern crate rustc;
 extern crate rustc_codegen_utils;
@@ -18,5 +22,10 @@ extern crate syntax;
 pub mod analyz;
 pub mod lib_util;
 pub mod link;
+pub mod test_script;
+#[doc(hidden)]
+pub mod test_fixtures;
+pub mod validate;
+pub mod wrapper;
 
 mod tar_stream;
@@ -19,23 +19,46 @@
 
 use std::borrow::Cow;
 use std::collections::{HashMap, HashSet, BTreeMap};
-use std::fs::File;
+use std::env;
+use std::fs::{self, File};
 use std::io::{self, Read, Write, Seek, SeekFrom, Cursor, BufWriter};
 use std::mem;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::sync::mpsc::{self, SyncSender, Receiver};
 use std::thread;
 
+use serde::Serialize;
 use serde_cbor::Value as CborValue;
 use serde_json::Value as JsonValue;
 use serde_cbor;
 use serde_json;
+use sha2::{Digest, Sha256};
 use tar;
 
 use crate::tar_stream::{TarStream, TarEntryStream};
 
+/// Version of the JSON schema.  This is recorded in every crate and in the linked output, so that
+/// consumers can detect files produced by an incompatible version of `mir-json`.
+///
+/// Bump this for any change that could break an existing consumer: removing or renaming a key,
+/// changing the type or shape of a value (such as a string becoming an object), or emitting
+/// entries a consumer couldn't previously see.  Purely additive changes, like a new key on an
+/// existing object, don't need a bump, since consumers ignore keys they don't know about.  For the
+/// same reason, a new key that presents existing information in a more convenient form (like
+/// `mutable` next to `mut` on locals, `is_const_fn` next to `is_const`, or `unwind` next to
+/// `cleanup`) is added alongside the old key rather than replacing it.
+///
+/// Version 5 changed `Ref` regions from strings to objects, renamed the intrinsics' ABI field to
+/// `fn_abi`, and started emitting non-object-safe traits.
+pub const SCHEMA_VERSION: u64 = 5;
+
 #[derive(Debug, Default, Serialize, Deserialize)]
 pub struct CrateIndex {
+    /// Schema version of the crate's JSON.  Indexes written before versioning was introduced
+    /// deserialize as version 0.
+    #[serde(default)]
+    pub version: u64,
+
     /// Name table.  Contains every string in the crate that looks like it might be an item name.
     /// `StringId`s are indexes into this table.
     pub names: Vec<String>,
@@ -43,6 +66,12 @@ pub struct CrateIndex {
     pub items: HashMap<StringId, ItemData>,
 
     pub roots: Vec<StringId>,
+
+    /// Hex-encoded SHA-256 hash of the crate's MIR.  This depends only on the contents of the
+    /// entries and the set of roots, not on the order they were emitted in, so it is stable
+    /// across rebuilds of identical source.  Empty for indexes that predate hashing.
+    #[serde(default)]
+    pub hash: String,
 }
 
 /// Metadata about a single item.
@@ -55,9 +84,25 @@ pub struct ItemData {
     /// All names mentioned in the bodies of this item.
     pub deps: Vec<StringId>,
 
+    /// Names of the functions this item refers to by `FnDef` type, which includes everything it
+    /// calls.  This is a subset of `deps`.  Every one of these should be defined in the linked
+    /// output.  `FnDef`s that `analyz` couldn't resolve to an instance are left out.
+    #[serde(default)]
+    pub calls: Vec<StringId>,
+
+    /// For impls, the names of the trait and self type.  The linker keeps an impl only if all of
+    /// these are live.
+    #[serde(default)]
+    pub impl_of: Vec<StringId>,
+
     /// The location of each entry for this item.  The first `u64` is the offset of the entry's
     /// JSON representation within `crates.json`, and the second `u64` is the length.
     pub locations: HashMap<EntryKind, (u64, u64)>,
+
+    /// Hex-encoded hash of each entry for this item, as computed by `entry_digest`.  The linker
+    /// compares these to detect conflicting definitions.  Empty for indexes that predate them.
+    #[serde(default)]
+    pub digests: HashMap<EntryKind, String>,
 }
 
 #[derive(Clone, Copy, PartialEq, Eq, Debug, Hash, Serialize, Deserialize)]
@@ -69,6 +114,7 @@ pub enum EntryKind {
     Vtable,
     Trait,
     Intrinsic,
+    Impl,
 }
 
 impl EntryKind {
@@ -81,6 +127,7 @@ impl EntryKind {
             Vtable => "vtable",
             Trait => "trait",
             Intrinsic => "intrinsic",
+            Impl => "impl",
         }
     }
 
@@ -93,12 +140,13 @@ impl EntryKind {
             Vtable => "vtables",
             Trait => "traits",
             Intrinsic => "intrinsics",
+            Impl => "impls",
         }
     }
 
     pub fn each() -> impl Iterator<Item = EntryKind> {
         use self::EntryKind::*;
-        [Fn, Adt, Static, Vtable, Trait, Intrinsic].iter().cloned()
+        [Fn, Adt, Static, Vtable, Trait, Intrinsic, Impl].iter().cloned()
     }
 }
 
@@ -135,6 +183,27 @@ impl InternTable {
 }
 
 
+// JSON formatting
+
+/// Check whether pretty-printed JSON output was requested, by setting `MIR_JSON_PRETTY`.
+pub fn pretty_json_enabled() -> bool {
+    env::var("MIR_JSON_PRETTY").map_or(false, |s| s != "0" && s != "")
+}
+
+/// Serialize `x` as JSON to `w`, pretty-printing it if `pretty` is set.
+pub fn write_json<W: Write, T: ?Sized + Serialize>(
+    w: W,
+    x: &T,
+    pretty: bool,
+) -> serde_json::Result<()> {
+    if pretty {
+        serde_json::to_writer_pretty(w, x)
+    } else {
+        serde_json::to_writer(w, x)
+    }
+}
+
+
 // IO adapter
 
 /// Writer that keeps a count of the number of bytes written so far.
@@ -161,9 +230,14 @@ impl<W: Write> Write for CountWrite<W> {
 #[derive(Default)]
 struct EmitterState {
     dep_map: HashMap<StringId, HashSet<StringId>>,
+    call_map: HashMap<StringId, HashSet<StringId>>,
+    impl_map: HashMap<StringId, Vec<StringId>>,
     entry_loc: HashMap<(StringId, EntryKind), (u64, u64)>,
     roots: HashSet<StringId>,
     intern: InternTable,
+    /// Hash of each entry's compact JSON serialization, tagged with its `EntryKind`.
+    entry_hashes: Vec<Vec<u8>>,
+    entry_digests: HashMap<(StringId, EntryKind), String>,
 }
 
 impl EmitterState {
@@ -176,6 +250,16 @@ impl EmitterState {
             },
 
             JsonValue::Object(ref m) => {
+                // An `FnDef` whose `Instance` failed to resolve has `"inst": null`, and its
+                // `defid` is the bare path of the generic item, which is never defined by name.
+                let is_resolved_fn_def = m.get("kind").and_then(|k| k.as_str()) == Some("FnDef")
+                    && m.get("inst") != Some(&JsonValue::Null);
+                if is_resolved_fn_def {
+                    if let Some(s) = m.get("defid").and_then(|d| d.as_str()) {
+                        let id2 = self.intern.intern(s.into());
+                        self.call_map.entry(id).or_insert_with(HashSet::new).insert(id2);
+                    }
+                }
                 for x in m.values() {
                     self.gather_deps(id, x);
                 }
@@ -201,6 +285,15 @@ impl EmitterState {
         // Collect dependencies
         let name_id = self.intern.intern(j["name"].as_str().unwrap().into());
         self.gather_deps(name_id, j);
+        self.hash_entry(kind, j);
+        self.entry_digests.insert((name_id, kind), entry_digest(j));
+        if kind == EntryKind::Impl {
+            let keys = [&j["trait"], &j["self_adt"]].iter()
+                .filter_map(|k| k.as_str())
+                .map(|k| self.intern.intern(k.into()))
+                .collect::<Vec<_>>();
+            self.impl_map.insert(name_id, keys);
+        }
 
         // Serialize the entry, and record its position.
         let (start, end) = write_entry(kind, j)?;
@@ -214,6 +307,15 @@ impl EmitterState {
         Ok(())
     }
 
+    fn hash_entry(&mut self, kind: EntryKind, j: &JsonValue) {
+        // `serde_json::Value` objects keep their keys sorted, so this serialization is canonical
+        // regardless of `pretty` or of the order fields were inserted in.
+        let mut h = Sha256::new();
+        h.input(kind.name().as_bytes());
+        h.input(&serde_json::to_vec(j).unwrap());
+        self.entry_hashes.push(h.result().to_vec());
+    }
+
     fn add_root(&mut self, s: Cow<str>) {
         let name_id = self.intern.intern(s);
         self.roots.insert(name_id);
@@ -229,23 +331,77 @@ impl EmitterState {
             data.deps = v.into_iter().collect::<Vec<_>>();
             data.deps.sort();
 
+            if let Some(calls) = self.call_map.remove(&name) {
+                data.calls = calls.into_iter().collect::<Vec<_>>();
+                data.calls.sort();
+            }
+
+            if let Some(keys) = self.impl_map.remove(&name) {
+                data.impl_of = keys;
+            }
+
             for kind in EntryKind::each() {
                 if let Some(&loc) = self.entry_loc.get(&(name, kind)) {
                     data.locations.insert(kind, loc);
                 }
+                if let Some(digest) = self.entry_digests.remove(&(name, kind)) {
+                    data.digests.insert(kind, digest);
+                }
             }
         }
 
         let mut roots = self.roots.into_iter().collect::<Vec<_>>();
         roots.sort();
 
-        CrateIndex { names, items, roots }
+        // Entries are emitted in hash-set order, which varies between runs, so combine the entry
+        // hashes in sorted order.  Roots are hashed by name, since `StringId`s are not stable.
+        self.entry_hashes.sort();
+        let mut root_names = roots.iter().map(|&id| &names[id] as &str).collect::<Vec<_>>();
+        root_names.sort();
+        let mut h = Sha256::new();
+        for eh in &self.entry_hashes {
+            h.input(eh);
+        }
+        for name in root_names {
+            h.input(name.as_bytes());
+            h.input(&[0]);
+        }
+        let hash = h.result().iter().map(|b| format!("{:02x}", b)).collect::<String>();
+
+        CrateIndex { version: SCHEMA_VERSION, names, items, roots, hash }
     }
 }
 
+/// Keys whose values depend on which crate emitted an entry, rather than on the item itself.
+/// `pretty` type names (from `MIR_JSON_PRETTY_TYPES`) are printed relative to the current crate,
+/// and a closure's `upvar_modes` are only available in the crate that defines it.
+const CRATE_DEPENDENT_KEYS: &[&str] = &["pretty", "upvar_modes"];
+
+fn strip_crate_dependent(j: &JsonValue) -> JsonValue {
+    match *j {
+        JsonValue::Array(ref a) => a.iter().map(strip_crate_dependent).collect(),
+        JsonValue::Object(ref m) => m.iter()
+            .filter(|&(k, _)| !CRATE_DEPENDENT_KEYS.contains(&(k as &str)))
+            .map(|(k, v)| (k.clone(), strip_crate_dependent(v)))
+            .collect::<serde_json::Map<_, _>>()
+            .into(),
+        ref x => x.clone(),
+    }
+}
+
+/// Hash an entry for comparison against other crates' entries for the same item.  The hash
+/// covers the entry's canonical compact serialization, so it doesn't depend on `MIR_JSON_PRETTY`,
+/// and it ignores `CRATE_DEPENDENT_KEYS`, so two crates that emit the same item agree on it.
+pub fn entry_digest(j: &JsonValue) -> String {
+    let mut h = Sha256::new();
+    h.input(&serde_json::to_vec(&strip_crate_dependent(j)).unwrap());
+    h.result().iter().map(|b| format!("{:02x}", b)).collect()
+}
+
 struct Emitter<W> {
     state: EmitterState,
     writer: CountWrite<W>,
+    pretty: bool,
 }
 
 impl<W: Write> Emitter<W> {
@@ -253,14 +409,16 @@ impl<W: Write> Emitter<W> {
         Emitter {
             state: EmitterState::default(),
             writer: CountWrite { w, count: 0 },
+            pretty: pretty_json_enabled(),
         }
     }
 
     fn emit_entry(&mut self, kind: EntryKind, j: &JsonValue) -> io::Result<()> {
         let writer = &mut self.writer;
+        let pretty = self.pretty;
         self.state.emit_entry(kind, j, |_, j| {
             let start = writer.count as u64;
-            serde_json::to_writer(&mut *writer, j)?;
+            write_json(&mut *writer, j, pretty)?;
             let end = writer.count as u64;
             Ok((start, end))
         })
@@ -290,22 +448,24 @@ impl<W: Write> Emitter<W> {
 
     pub fn emit_crate(&mut self, j: &JsonValue) -> io::Result<()> {
         write!(self.writer, "{{")?;
-        self.emit_table_from(EntryKind::Fn, j);
+        self.emit_table_from(EntryKind::Fn, j)?;
         write!(self.writer, ",")?;
-        self.emit_table_from(EntryKind::Adt, j);
+        self.emit_table_from(EntryKind::Adt, j)?;
         write!(self.writer, ",")?;
-        self.emit_table_from(EntryKind::Static, j);
+        self.emit_table_from(EntryKind::Static, j)?;
         write!(self.writer, ",")?;
-        self.emit_table_from(EntryKind::Vtable, j);
+        self.emit_table_from(EntryKind::Vtable, j)?;
         write!(self.writer, ",")?;
-        self.emit_table_from(EntryKind::Trait, j);
+        self.emit_table_from(EntryKind::Trait, j)?;
         write!(self.writer, ",")?;
-        self.emit_table_from(EntryKind::Intrinsic, j);
+        self.emit_table_from(EntryKind::Intrinsic, j)?;
         write!(self.writer, ",")?;
-        write!(self.writer, "\"impls\":[]")?;
+        self.emit_table_from(EntryKind::Impl, j)?;
+        write!(self.writer, ",")?;
+        write!(self.writer, "\"version\":{}", SCHEMA_VERSION)?;
         write!(self.writer, ",")?;
         write!(self.writer, "\"roots\":")?;
-        serde_json::to_writer(&mut self.writer, &j["roots"])?;
+        write_json(&mut self.writer, &j["roots"], self.pretty)?;
         write!(self.writer, "}}")?;
         self.writer.flush()?;
 
@@ -323,7 +483,16 @@ impl<W: Write> Emitter<W> {
     }
 }
 
-pub fn write_indexed_crate<W>(out: W, j: &JsonValue) -> serde_cbor::Result<()>
+/// Write `j` to `out` in the indexed crate format.  Returns the hash of the crate's MIR, as recorded
+/// in the index.
+pub fn write_indexed_crate<W>(out: W, j: &JsonValue) -> serde_cbor::Result<String>
+where W: Write + Send + 'static {
+    let (_, hash) = build_indexed_crate(out, j)?;
+    Ok(hash)
+}
+
+/// Like `write_indexed_crate`, but also gives back `out` once the archive is finished.
+pub fn build_indexed_crate<W>(out: W, j: &JsonValue) -> serde_cbor::Result<(W, String)>
 where W: Write + Send + 'static {
     // Serialize the two files to byte arrays.  This is needed so their lengths will be known when
     // creating the archive.
@@ -332,6 +501,7 @@ where W: Write + Send + 'static {
     emitter.emit_crate(j)?;
 
     let index = emitter.finish();
+    let hash = index.hash.clone();
     let index_buf = serde_cbor::to_vec(&index)?;
 
     let mut tar = tar::Builder::new(out);
@@ -347,8 +517,37 @@ where W: Write + Send + 'static {
     index_hdr.set_mode(0o644);
     tar.append_data(&mut index_hdr, "index.cbor", Cursor::new(index_buf))?;
 
-    tar.finish()?;
+    // Any other top-level keys are crate info sections.
+    let info = j.as_object().map_or_else(BTreeMap::new, |m| {
+        m.iter()
+            .filter(|&(k, _)| {
+                k != "roots" && k != "version" &&
+                    EntryKind::each().all(|kind| kind.table_name() != k)
+            })
+            .map(|(k, v)| (k.clone(), v.clone()))
+            .collect::<BTreeMap<_, _>>()
+    });
+    if info.len() > 0 {
+        let info_buf = serde_json::to_vec(&info)
+            .map_err(|e| -> io::Error { e.into() })?;
+        let mut info_hdr = tar::Header::new_ustar();
+        info_hdr.set_size(info_buf.len() as u64);
+        info_hdr.set_mode(0o644);
+        tar.append_data(&mut info_hdr, "info.json", Cursor::new(info_buf))?;
+    }
+
+    let out = tar.into_inner()?;
 
+    Ok((out, hash))
+}
+
+/// Write `hash` to `<mir_path>.sha256`, next to the `.mir` file itself.  This lets tools check
+/// whether a crate's MIR changed without opening the archive.
+pub fn write_hash_file(mir_path: &Path, hash: &str) -> io::Result<()> {
+    let mut path = mir_path.as_os_str().to_owned();
+    path.push(".sha256");
+    let mut f = File::create(path)?;
+    writeln!(f, "{}", hash)?;
     Ok(())
 }
 
@@ -382,11 +581,78 @@ pub fn read_crate_index<R: Read + Seek>(mut input: R) -> serde_cbor::Result<(Cra
 }
 
 
+/// Read the crate info sections from an indexed crate.  These are stored in a separate `info.json`
+/// entry, which is omitted when the crate has no info sections.
+pub fn read_crate_info<R: Read + Seek>(
+    mut input: R,
+) -> serde_cbor::Result<BTreeMap<String, JsonValue>> {
+    input.seek(SeekFrom::Start(0))?;
+    let mut tar = tar::Archive::new(input);
+    for entry in tar.entries()? {
+        let entry = entry?;
+        if entry.path()? == Path::new("info.json") {
+            return Ok(serde_json::from_reader(entry)
+                .map_err(|e| -> io::Error { e.into() })?);
+        }
+    }
+    Ok(BTreeMap::new())
+}
+
+/// Crate info sections that hold tallies, which are summed when merging crates.
+const TALLY_INFO_SECTIONS: &[&str] = &["unsupported_predicates"];
+
+/// Merge crate info sections from several crates.  For tally sections (`TALLY_INFO_SECTIONS`),
+/// counts present in both are summed.  Array sections (like `referenced_types`) are combined,
+/// dropping duplicates.  For anything else, such as `data_layout`, the first crate's value wins.
+pub fn merge_crate_info(
+    dest: &mut BTreeMap<String, JsonValue>,
+    src: BTreeMap<String, JsonValue>,
+) {
+    for (key, val) in src {
+        let is_tally = TALLY_INFO_SECTIONS.contains(&(&key as &str));
+        let dest_val = match dest.get_mut(&key) {
+            Some(x) => x,
+            None => {
+                dest.insert(key, val);
+                continue;
+            },
+        };
+        match (dest_val, val) {
+            (&mut JsonValue::Object(ref mut dest_obj), JsonValue::Object(src_obj)) if is_tally => {
+                for (k, v) in src_obj {
+                    if let Some(old) = dest_obj.get_mut(&k) {
+                        if let (Some(a), Some(b)) = (old.as_u64(), v.as_u64()) {
+                            *old = (a + b).into();
+                        }
+                        continue;
+                    }
+                    dest_obj.insert(k, v);
+                }
+            },
+            (&mut JsonValue::Array(ref mut dest_arr), JsonValue::Array(src_arr)) => {
+                // `JsonValue` isn't `Hash`, so dedup by serialized form.
+                let mut seen = dest_arr.iter().map(|v| v.to_string()).collect::<HashSet<_>>();
+                for v in src_arr {
+                    if seen.insert(v.to_string()) {
+                        dest_arr.push(v);
+                    }
+                }
+            },
+            _ => {},
+        }
+    }
+}
+
+
 // JSON output modes
 
 pub trait JsonOutput {
     fn emit(&mut self, kind: EntryKind, j: serde_json::Value) -> io::Result<()>;
     fn add_root(&mut self, name: String) -> io::Result<()>;
+    /// Add an extra top-level section to the crate output, such as `source_files`.  Unlike the
+    /// item tables, these sections are not indexed, and they get merged across crates when
+    /// linking.
+    fn add_crate_info(&mut self, key: &str, j: serde_json::Value) -> io::Result<()>;
 }
 
 #[derive(Default)]
@@ -408,8 +674,13 @@ pub struct Output {
     /// Provides the `instance` for each monomorphized function used in the crate that doesn't have
     /// a MIR body.
     pub intrinsics: Vec<serde_json::Value>,
+    /// All impl blocks defined in the crate, giving the self type, the implemented trait (if
+    /// any), and the associated items of each.
+    pub impls: Vec<serde_json::Value>,
     /// Entry points for this crate.
     pub roots: Vec<String>,
+    /// Extra top-level sections, such as `source_files`.
+    pub crate_info: BTreeMap<String, serde_json::Value>,
 }
 
 impl JsonOutput for Output {
@@ -421,6 +692,7 @@ impl JsonOutput for Output {
             EntryKind::Vtable => self.vtables.push(j),
             EntryKind::Trait => self.traits.push(j),
             EntryKind::Intrinsic => self.intrinsics.push(j),
+            EntryKind::Impl => self.impls.push(j),
         }
         Ok(())
     }
@@ -429,6 +701,48 @@ impl JsonOutput for Output {
         self.roots.push(name);
         Ok(())
     }
+
+    fn add_crate_info(&mut self, key: &str, j: serde_json::Value) -> io::Result<()> {
+        self.crate_info.insert(key.to_owned(), j);
+        Ok(())
+    }
+}
+
+
+/// `JsonOutput` adapter that checks that each entry survives a round trip through its serialized
+/// form unchanged, and panics if it doesn't.  This catches values that can't be represented
+/// faithfully in JSON.  The check is skipped when `enabled` is false.
+pub struct RoundTripCheck<O> {
+    pub inner: O,
+    pub enabled: bool,
+}
+
+fn check_round_trip(kind: EntryKind, j: &JsonValue) -> io::Result<()> {
+    let s = serde_json::to_string(j).map_err(|e| -> io::Error { e.into() })?;
+    let j2: JsonValue = serde_json::from_str(&s).map_err(|e| -> io::Error { e.into() })?;
+    let s2 = serde_json::to_string(&j2).map_err(|e| -> io::Error { e.into() })?;
+    if j2 != *j || s2 != s {
+        panic!("{:?} entry {} does not round-trip through JSON:\n  {}\n  {}",
+            kind, j["name"], s, s2);
+    }
+    Ok(())
+}
+
+impl<O: JsonOutput> JsonOutput for RoundTripCheck<O> {
+    fn emit(&mut self, kind: EntryKind, j: serde_json::Value) -> io::Result<()> {
+        if self.enabled {
+            check_round_trip(kind, &j)?;
+        }
+        self.inner.emit(kind, j)
+    }
+
+    fn add_root(&mut self, name: String) -> io::Result<()> {
+        self.inner.add_root(name)
+    }
+
+    fn add_crate_info(&mut self, key: &str, j: serde_json::Value) -> io::Result<()> {
+        self.inner.add_crate_info(key, j)
+    }
 }
 
 
@@ -442,6 +756,9 @@ pub struct StreamingEmitter<W> {
     /// producing an entire crate via `emit_crate`.
     inner: Emitter<W>,
     len: usize,
+    /// Crate info sections are small compared to the MIR entries, so we keep them in memory and
+    /// write them out as a separate archive entry at the end.
+    crate_info: BTreeMap<String, serde_json::Value>,
 }
 
 impl<W: Write> StreamingEmitter<W> {
@@ -449,6 +766,7 @@ impl<W: Write> StreamingEmitter<W> {
         let mut se = StreamingEmitter {
             inner: Emitter::new(w),
             len: 0,
+            crate_info: BTreeMap::new(),
         };
         // Write the opening `[` through the inner `CountWrite` so that the index will contain
         // accurate offsets.
@@ -456,12 +774,12 @@ impl<W: Write> StreamingEmitter<W> {
         Ok(se)
     }
 
-    pub fn finish(mut self) -> io::Result<(W, CrateIndex)> {
+    pub fn finish(mut self) -> io::Result<(W, CrateIndex, BTreeMap<String, serde_json::Value>)> {
         // TODO: expose this through a method on Emitter rather than reaching into its internal
         // state.
         let index = self.inner.state.finish();
         write!(self.inner.writer, "]")?;
-        Ok((self.inner.writer.w, index))
+        Ok((self.inner.writer.w, index, self.crate_info))
     }
 }
 
@@ -481,6 +799,11 @@ impl<W: Write> JsonOutput for StreamingEmitter<W> {
         self.inner.add_root(name.into());
         Ok(())
     }
+
+    fn add_crate_info(&mut self, key: &str, j: serde_json::Value) -> io::Result<()> {
+        self.crate_info.insert(key.to_owned(), j);
+        Ok(())
+    }
 }
 
 
@@ -498,6 +821,10 @@ impl JsonOutput for MirStream {
     fn add_root(&mut self, name: String) -> io::Result<()> {
         self.emitter.add_root(name)
     }
+
+    fn add_crate_info(&mut self, key: &str, j: serde_json::Value) -> io::Result<()> {
+        self.emitter.add_crate_info(key, j)
+    }
 }
 
 fn make_tar_entry(path: &str) -> tar::Header {
@@ -518,13 +845,284 @@ pub fn start_streaming(path: &Path) -> io::Result<MirStream> {
     Ok(MirStream { emitter })
 }
 
-pub fn finish_streaming(ms: MirStream) -> serde_cbor::Result<()> {
-    let (json_entry, index) = ms.emitter.finish()?;
+/// Finish writing the `.mir` file.  Returns the hash of the crate's MIR, as recorded in the index.
+pub fn finish_streaming(ms: MirStream) -> serde_cbor::Result<String> {
+    let (json_entry, index, crate_info) = ms.emitter.finish()?;
     let tar = json_entry.finish_entry()?;
     let mut index_entry = tar.start_entry(make_tar_entry("index.cbor"))?;
     serde_cbor::to_writer(&mut index_entry, &index)?;
-    let tar = index_entry.finish_entry()?;
+    let mut tar = index_entry.finish_entry()?;
+    if crate_info.len() > 0 {
+        let mut info_entry = tar.start_entry(make_tar_entry("info.json"))?;
+        write_json(&mut info_entry, &crate_info, pretty_json_enabled())
+            .map_err(|e| -> io::Error { e.into() })?;
+        tar = info_entry.finish_entry()?;
+    }
     let mut w = tar.finish()?;
     w.flush()?;
-    Ok(())
+    Ok(index.hash)
+}
+
+
+// Split output, with one file per function.
+
+/// Get the directory to write a crate's split output to, if `MIR_JSON_SPLIT` is set.  Each crate
+/// gets a subdirectory named after its `.mir` file, so builds of different crates (or of the same
+/// crate with different metadata) don't collide.
+pub fn split_dir(mir_path: &Path) -> Option<PathBuf> {
+    let dir = env::var_os("MIR_JSON_SPLIT")?;
+    Some(PathBuf::from(dir).join(mir_path.file_stem()?))
+}
+
+/// Output for `MIR_JSON_SPLIT`.  Each `fns` entry is written immediately to its own file in `dir`.
+/// Everything else is collected in an `Output`, which `finish_split` returns so the caller can
+/// write it to `crate.json` (with an empty `fns` table).
+pub struct SplitOutput {
+    dir: PathBuf,
+    rest: Output,
+    /// Maps each function name to the name of its file in `dir`.
+    files: BTreeMap<String, String>,
+}
+
+/// Get the file name for the function `name`.  Names contain characters that aren't safe in file
+/// names (and can be very long), so we keep a readable prefix and add a hash of the full name to
+/// make the file name unique.
+fn split_file_name(name: &str) -> String {
+    let mut prefix = name.chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '_' || c == '-' { c } else { '_' })
+        .collect::<String>();
+    prefix.truncate(100);
+    let mut h = Sha256::new();
+    h.input(name.as_bytes());
+    let hash = h.result().iter().take(8).map(|b| format!("{:02x}", b)).collect::<String>();
+    format!("{}-{}.json", prefix, hash)
+}
+
+impl SplitOutput {
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+}
+
+impl JsonOutput for SplitOutput {
+    fn emit(&mut self, kind: EntryKind, j: serde_json::Value) -> io::Result<()> {
+        if kind != EntryKind::Fn {
+            return self.rest.emit(kind, j);
+        }
+        let name = match j["name"].as_str() {
+            Some(name) => name.to_owned(),
+            None => return Err(invalid_data(format!("fn entry has no name: {}", j))),
+        };
+        let file_name = split_file_name(&name);
+        let mut f = BufWriter::new(File::create(self.dir.join(&file_name))?);
+        write_json(&mut f, &j, pretty_json_enabled())
+            .map_err(|e| -> io::Error { e.into() })?;
+        f.flush()?;
+        self.files.insert(name, file_name);
+        Ok(())
+    }
+
+    fn add_root(&mut self, name: String) -> io::Result<()> {
+        self.rest.add_root(name)
+    }
+
+    fn add_crate_info(&mut self, key: &str, j: serde_json::Value) -> io::Result<()> {
+        self.rest.add_crate_info(key, j)
+    }
+}
+
+pub fn start_split(dir: &Path) -> io::Result<SplitOutput> {
+    fs::create_dir_all(dir)?;
+    Ok(SplitOutput {
+        dir: dir.to_owned(),
+        rest: Output::default(),
+        files: BTreeMap::new(),
+    })
+}
+
+/// Write `index.json`, which maps the name of each function to its file, and return the rest of
+/// the crate's output.
+pub fn finish_split(so: SplitOutput) -> io::Result<Output> {
+    let index = json!({
+        "version": SCHEMA_VERSION,
+        "fns": so.files,
+    });
+    let mut f = BufWriter::new(File::create(so.dir.join("index.json"))?);
+    write_json(&mut f, &index, pretty_json_enabled())
+        .map_err(|e| -> io::Error { e.into() })?;
+    f.flush()?;
+    Ok(so.rest)
+}
+
+/// Read a split crate directory back into a single crate JSON object, in the same form that
+/// `write_indexed_crate` takes.
+/// Returns an `InvalidData` error if `index.json` or `crate.json` is malformed.
+pub fn read_split_crate(dir: &Path) -> io::Result<JsonValue> {
+    let read = |name: &str| -> io::Result<JsonValue> {
+        let f = io::BufReader::new(File::open(dir.join(name))?);
+        Ok(serde_json::from_reader(f)?)
+    };
+    let mut j = read("crate.json")?;
+    let index = read("index.json")?;
+    let files = index["fns"].as_object().ok_or_else(|| invalid_data(
+        format!("{}: index.json has no fns table", dir.display())))?;
+    let mut fns = Vec::with_capacity(files.len());
+    for (name, file_name) in files {
+        let file_name = file_name.as_str().ok_or_else(|| invalid_data(format!(
+            "{}: index.json has a non-string file name for {}", dir.display(), name)))?;
+        fns.push(read(file_name)?);
+    }
+    match j.as_object_mut() {
+        Some(obj) => { obj.insert("fns".into(), fns.into()); },
+        None => return Err(invalid_data(
+            format!("{}: crate.json is not an object", dir.display()))),
+    }
+    Ok(j)
+}
+
+fn invalid_data(msg: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+/// Convert a split crate directory to the indexed `.mir` format, in memory.
+pub fn split_crate_to_indexed(dir: &Path) -> serde_cbor::Result<Vec<u8>> {
+    let j = read_split_crate(dir)?;
+    let (buf, _) = build_indexed_crate(Vec::new(), &j)?;
+    Ok(buf)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures::{crate_json, fn_entry, temp_dir};
+
+    fn crate_hash(j: &JsonValue, pretty: bool) -> String {
+        let mut emitter = Emitter::new(Vec::new());
+        emitter.pretty = pretty;
+        emitter.emit_crate(j).unwrap();
+        emitter.finish().hash
+    }
+
+    #[test]
+    fn hash_ignores_order_and_formatting() {
+        let f = fn_entry("c::f", json!("ty::u8"), &[]);
+        let g = fn_entry("c::g", json!("ty::u16"), &[]);
+        let a = crate_json(vec![f.clone(), g.clone()], &["c::f"]);
+        let b = crate_json(vec![g, f], &["c::f"]);
+        let h = crate_hash(&a, false);
+        assert_eq!(h.len(), 64);
+        assert_eq!(h, crate_hash(&b, false));
+        assert_eq!(h, crate_hash(&a, true));
+    }
+
+    #[test]
+    fn round_trip_check_passes_entries_through() {
+        let mut out = RoundTripCheck { inner: Output::default(), enabled: true };
+        let f = fn_entry("c::f", json!("ty::u8"), &[]);
+        let adt = json!({"name": "c::S", "kind": "Struct", "variants": [], "size": 1.5e300});
+        out.emit(EntryKind::Fn, f.clone()).unwrap();
+        out.emit(EntryKind::Adt, adt.clone()).unwrap();
+        out.add_root("c::f".to_owned()).unwrap();
+        out.add_crate_info("entry_fn", JsonValue::Null).unwrap();
+
+        let out = out.inner;
+        assert_eq!(out.fns, [f]);
+        assert_eq!(out.adts, [adt]);
+        assert_eq!(out.roots, ["c::f"]);
+        assert_eq!(out.crate_info["entry_fn"], JsonValue::Null);
+    }
+
+    #[test]
+    fn hash_changes_with_contents() {
+        let a = crate_json(vec![fn_entry("c::f", json!("ty::u8"), &[])], &["c::f"]);
+        let b = crate_json(vec![fn_entry("c::f", json!("ty::u16"), &[])], &["c::f"]);
+        assert_ne!(crate_hash(&a, false), crate_hash(&b, false));
+    }
+
+    #[test]
+    fn split_round_trip() {
+        let dir = temp_dir("split");
+        let fns = vec![
+            fn_entry("c::f", json!("ty::u8"), &[]),
+            fn_entry("c::g", json!("ty::u16"), &[]),
+            fn_entry("c::<impl Foo<'a>>::h", json!("ty::u32"), &[]),
+        ];
+        let adt = json!({"name": "c::S", "kind": "Struct", "variants": []});
+
+        let mut so = start_split(&dir).unwrap();
+        for f in &fns {
+            so.emit(EntryKind::Fn, f.clone()).unwrap();
+        }
+        so.emit(EntryKind::Adt, adt.clone()).unwrap();
+        so.add_root("c::f".to_owned()).unwrap();
+        let rest = finish_split(so).unwrap();
+        assert!(rest.fns.is_empty());
+        assert_eq!(rest.adts, [adt.clone()]);
+        assert_eq!(rest.roots, ["c::f"]);
+
+        let index: JsonValue =
+            serde_json::from_slice(&fs::read(dir.join("index.json")).unwrap()).unwrap();
+        let files = index["fns"].as_object().unwrap();
+        let mut names = files.keys().cloned().collect::<Vec<_>>();
+        names.sort();
+        assert_eq!(names, ["c::<impl Foo<'a>>::h", "c::f", "c::g"]);
+        for file_name in files.values() {
+            assert!(dir.join(file_name.as_str().unwrap()).is_file());
+        }
+
+        let mut crate_j = crate_json(vec![], &["c::f"]);
+        crate_j["adts"] = rest.adts.into();
+        write_json(&mut File::create(dir.join("crate.json")).unwrap(), &crate_j, false).unwrap();
+        let j = read_split_crate(&dir).unwrap();
+        let mut read_fns = j["fns"].as_array().unwrap().clone();
+        read_fns.sort_by_key(|f| f["name"].as_str().unwrap().to_owned());
+        let mut expected = fns.clone();
+        expected.sort_by_key(|f| f["name"].as_str().unwrap().to_owned());
+        assert_eq!(read_fns, expected);
+        assert_eq!(j["adts"], json!([adt]));
+        assert_eq!(j["roots"], json!(["c::f"]));
+
+        let buf = split_crate_to_indexed(&dir).unwrap();
+        let (index, _) = read_crate_index(Cursor::new(buf)).unwrap();
+        assert_eq!(index.items.len(), 4);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn split_errors() {
+        let dir = temp_dir("split-errors");
+        let mut so = start_split(&dir).unwrap();
+        let err = so.emit(EntryKind::Fn, json!({"args": []})).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+
+        let write = |name: &str, j: JsonValue| {
+            write_json(&mut File::create(dir.join(name)).unwrap(), &j, false).unwrap();
+        };
+        let check = |msg: &str| {
+            let err = read_split_crate(&dir).unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+            assert!(err.to_string().contains(msg), "{}", err);
+        };
+        write("crate.json", json!({"roots": []}));
+        write("index.json", json!({"version": SCHEMA_VERSION}));
+        check("no fns table");
+        write("index.json", json!({"fns": {"c::f": 1}}));
+        check("non-string file name for c::f");
+        write("index.json", json!({"fns": {}}));
+        write("crate.json", json!([]));
+        check("crate.json is not an object");
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn hash_matches_written_index() {
+        let j = crate_json(vec![fn_entry("c::f", json!("ty::u8"), &[])], &["c::f"]);
+        let (buf, hash) = build_indexed_crate(Vec::new(), &j).unwrap();
+        let (index, _) = read_crate_index(Cursor::new(buf)).unwrap();
+        assert_eq!(index.hash, hash);
+        assert_eq!(index.version, SCHEMA_VERSION);
+    }
 }
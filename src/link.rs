@@ -1,6 +1,14 @@
-use std::collections::{HashMap, HashSet};
-use std::io::{self, Read, Write, Seek, SeekFrom};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::env;
+use std::error::Error;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, Cursor, Read, Write, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
 
+use flate2::Compression;
+use flate2::write::GzEncoder;
+use rayon::prelude::*;
 use serde_cbor::Value as CborValue;
 use serde_json::Value as JsonValue;
 use serde_cbor;
@@ -9,16 +17,111 @@ use serde_json;
 use crate::lib_util::{self, CrateIndex, InternTable, EntryKind, StringId};
 
 
-fn read_crates<R: Read + Seek>(
+/// Errors that can occur while linking.  Inputs are identified by their position in the `inputs`
+/// slice, since the linker only sees readers, not file names.
+#[derive(Debug)]
+pub enum LinkError {
+    /// Failed to write the output (or to seek or copy from an input after it was parsed).
+    Io(io::Error),
+    /// An input's index or crate info couldn't be read or parsed.
+    Parse { input: usize, source: serde_cbor::Error },
+    /// Two inputs have different `kind` entries for the same item, as determined by comparing
+    /// their `ItemData::digests`.
+    Conflict { name: String, kind: EntryKind, a: usize, b: usize },
+    /// An input was produced by a version of `mir-json` with a different schema.
+    VersionMismatch { input: usize, found: u64, expected: u64 },
+    /// Live items call functions that aren't defined in any input.  Each pair is a callee and one
+    /// of its callers.
+    MissingDefinition { missing: Vec<(String, String)> },
+}
+
+impl fmt::Display for LinkError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            LinkError::Io(ref e) => write!(f, "I/O error: {}", e),
+            LinkError::Parse { input, ref source } =>
+                write!(f, "failed to read input {}: {}", input, source),
+            LinkError::Conflict { ref name, kind, a, b } =>
+                write!(f, "inputs {} and {} have conflicting {} entries for {}",
+                    a, b, kind.name(), name),
+            LinkError::VersionMismatch { input, found, expected } =>
+                write!(f, "input {} has schema version {}, but this linker expects version {}; \
+                    try rebuilding it with the current mir-json", input, found, expected),
+            LinkError::MissingDefinition { ref missing } => {
+                write!(f, "{} undefined function reference(s) in linked output:", missing.len())?;
+                for &(ref callee, ref caller) in missing {
+                    write!(f, "\n  {} (referenced by {})", callee, caller)?;
+                }
+                Ok(())
+            },
+        }
+    }
+}
+
+impl Error for LinkError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match *self {
+            LinkError::Io(ref e) => Some(e),
+            LinkError::Parse { ref source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for LinkError {
+    fn from(e: io::Error) -> LinkError {
+        LinkError::Io(e)
+    }
+}
+
+impl From<serde_json::Error> for LinkError {
+    fn from(e: serde_json::Error) -> LinkError {
+        LinkError::Io(e.into())
+    }
+}
+
+impl LinkError {
+    /// Format this error like `Display`, but name inputs using `paths` instead of their indexes.
+    pub fn describe(&self, paths: &[PathBuf]) -> String {
+        match *self {
+            LinkError::Parse { input, ref source } =>
+                format!("failed to read {}: {}", paths[input].display(), source),
+            LinkError::VersionMismatch { input, found, expected } =>
+                format!("{} has schema version {}, but this linker expects version {}; \
+                    try rebuilding it with the current mir-json",
+                    paths[input].display(), found, expected),
+            LinkError::Conflict { ref name, kind, a, b } =>
+                format!("{} and {} have conflicting {} entries for {}",
+                    paths[a].display(), paths[b].display(), kind.name(), name),
+            ref e => e.to_string(),
+        }
+    }
+}
+
+
+/// Read the index of each input crate.  The inputs are independent, so we read them in parallel.
+/// The results are in the same order as `inputs`, so crate numbers are the same as in the serial
+/// case.
+fn read_crates<R: Read + Seek + Send>(
     inputs: &mut [R],
-) -> serde_cbor::Result<(Vec<CrateIndex>, Vec<u64>)> {
-    let mut indexes = Vec::with_capacity(inputs.len());
-    let mut json_offsets = Vec::with_capacity(inputs.len());
-    for r in inputs.iter_mut() {
-        let (i, j) = lib_util::read_crate_index(r)?;
-        indexes.push(i);
-        json_offsets.push(j);
+) -> Result<(Vec<CrateIndex>, Vec<u64>), LinkError> {
+    let results = inputs.par_iter_mut()
+        .enumerate()
+        .map(|(i, r)| lib_util::read_crate_index(r)
+            .map_err(|e| LinkError::Parse { input: i, source: e }))
+        .collect::<Result<Vec<_>, _>>()?;
+    let (indexes, json_offsets): (Vec<CrateIndex>, Vec<u64>) = results.into_iter().unzip();
+
+    for (i, index) in indexes.iter().enumerate() {
+        if index.version != lib_util::SCHEMA_VERSION {
+            return Err(LinkError::VersionMismatch {
+                input: i,
+                found: index.version,
+                expected: lib_util::SCHEMA_VERSION,
+            });
+        }
     }
+
     Ok((indexes, json_offsets))
 }
 
@@ -66,38 +169,202 @@ fn collect_roots(
 }
 
 
-/// Combine the contents of `ocs`, producing a combined JSON crate data object as the result.
-pub fn link_crates<R, W>(inputs: &mut [R], mut output: W) -> serde_cbor::Result<()>
-where R: Read + Seek, W: Write {
-    let (indexes, json_offsets) = read_crates(inputs)?;
-    let (it, defs, translate) = assign_global_ids(&indexes);
-    let roots = collect_roots(&indexes, &translate);
+/// Options controlling `link_crates_with`.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct LinkOptions {
+    /// Allow calls to functions that aren't defined in any input, and conflicting entries (the
+    /// first input's entry is used).  This is useful for linking only part of a program.
+    pub partial: bool,
+    /// Keep only the items reachable from the roots (the test functions of the top-level crate)
+    /// and from each input's entry point (its `main`).  Reachability follows every name mentioned
+    /// in an item's JSON, which includes call targets and types.  Calls through trait objects are
+    /// covered too: the unsizing cast that creates the trait object names its vtable, and the
+    /// vtable names each method implementation.  Without this, every entry of every input is
+    /// kept.
+    pub prune: bool,
+}
 
+impl LinkOptions {
+    /// Read the options from the environment: `MIR_JSON_PARTIAL_LINK` sets `partial`, and
+    /// `MIR_JSON_PRUNE` (set to anything other than `0`) sets `prune`.
+    pub fn from_env() -> LinkOptions {
+        LinkOptions {
+            partial: env::var("MIR_JSON_PARTIAL_LINK").is_ok(),
+            prune: env::var("MIR_JSON_PRUNE").map_or(false, |s| s != "0" && s != ""),
+        }
+    }
+}
 
-    let mut seen_names = HashSet::new();
-    let mut worklist = roots.clone();
-    while let Some(id) = worklist.pop() {
-        // Look for deps in all crates.  It seems like different sets of entries for an item can
-        // appear in different crates, though I'm not sure why.
+
+/// Combine the contents of `inputs`, writing a combined JSON crate data object to `output`.
+///
+/// Only the crate indexes are held in memory.  The serialized entries themselves are copied
+/// directly from the inputs to `output` as each table is written, so memory use doesn't grow with
+/// the size of the function bodies being linked.
+///
+/// `inputs` can be any seekable readers, not just files.  In particular, in-memory crates can be
+/// linked by wrapping them in `io::Cursor`.  `Seek` is required because entries are copied from
+/// the inputs by byte range, in an order determined by the index rather than by their position in
+/// the input.  `Send` is required because the indexes are read in parallel.
+///
+/// This checks that every function called from the output's items is defined in one of the
+/// inputs, and that inputs defining the same entry agree on its contents, and fails without
+/// writing anything if either check fails.  Use `link_crates_partial` to skip the checks.
+pub fn link_crates<R, W>(inputs: &mut [R], output: W) -> Result<(), LinkError>
+where R: Read + Seek + Send, W: Write {
+    link_crates_with(inputs, output, LinkOptions::default())
+}
+
+/// Like `link_crates`, but with `LinkOptions::partial` set.
+pub fn link_crates_partial<R, W>(inputs: &mut [R], output: W) -> Result<(), LinkError>
+where R: Read + Seek + Send, W: Write {
+    link_crates_with(inputs, output, LinkOptions { partial: true, ..LinkOptions::default() })
+}
+
+/// Check that each function called by a live item has a `Fn` or `Intrinsic` entry in some crate.
+fn check_calls_defined(
+    indexes: &[CrateIndex],
+    it: &InternTable,
+    defs: &HashMap<StringId, Vec<(usize, StringId)>>,
+    translate: &HashMap<(usize, StringId), StringId>,
+    live: &HashSet<StringId>,
+) -> Result<(), LinkError> {
+    let is_fn_defined = |id: StringId| {
+        defs.get(&id).map_or(false, |def_list| def_list.iter().any(|&(crate_num, local_id)| {
+            let locs = &indexes[crate_num].items[&local_id].locations;
+            locs.contains_key(&EntryKind::Fn) || locs.contains_key(&EntryKind::Intrinsic)
+        }))
+    };
+
+    let mut missing: Vec<(StringId, StringId)> = Vec::new();
+    for &id in live {
         let def_list = match defs.get(&id) {
             Some(x) => x,
             None => continue,
         };
         for &(crate_num, local_id) in def_list {
-            for &local_id2 in &indexes[crate_num].items[&local_id].deps {
-                let id2 = translate[&(crate_num, local_id2)];
-                if seen_names.insert(id2) {
-                    worklist.push(id2);
+            for &local_callee in &indexes[crate_num].items[&local_id].calls {
+                let callee = translate[&(crate_num, local_callee)];
+                if !is_fn_defined(callee) {
+                    missing.push((callee, id));
                 }
             }
         }
     }
 
+    if missing.len() == 0 {
+        return Ok(());
+    }
+    missing.sort();
+    missing.dedup();
+    Err(LinkError::MissingDefinition {
+        missing: missing.into_iter()
+            .map(|(callee, caller)| (it.name(callee).to_owned(), it.name(caller).to_owned()))
+            .collect(),
+    })
+}
+
+/// Find the names of every item reachable from `seeds`, for `LinkOptions::prune`.
+fn live_names(
+    indexes: &[CrateIndex],
+    defs: &HashMap<StringId, Vec<(usize, StringId)>>,
+    translate: &HashMap<(usize, StringId), StringId>,
+    seeds: Vec<StringId>,
+) -> HashSet<StringId> {
+    // Nothing refers to impls by name, so they aren't reached through `deps`.  Instead, we keep
+    // each impl whose trait and self type are both live.  A kept impl's own deps (its methods,
+    // and the types and traits in its signature) are then walked like any other item's, and may
+    // make more impls live, so we repeat until no new impls are found.
+    let mut impls = Vec::new();
+    for (&id, def_list) in defs {
+        for &(crate_num, local_id) in def_list {
+            let data = &indexes[crate_num].items[&local_id];
+            if data.locations.contains_key(&EntryKind::Impl) {
+                let keys = data.impl_of.iter()
+                    .map(|&k| translate[&(crate_num, k)])
+                    .collect::<Vec<_>>();
+                impls.push((id, keys));
+            }
+        }
+    }
+
+    let mut seen_names = seeds.iter().cloned().collect::<HashSet<_>>();
+    let mut worklist = seeds;
+    loop {
+        while let Some(id) = worklist.pop() {
+            // Look for deps in all crates.  It seems like different sets of entries for an item
+            // can appear in different crates, though I'm not sure why.
+            let def_list = match defs.get(&id) {
+                Some(x) => x,
+                None => continue,
+            };
+            for &(crate_num, local_id) in def_list {
+                for &local_id2 in &indexes[crate_num].items[&local_id].deps {
+                    let id2 = translate[&(crate_num, local_id2)];
+                    if seen_names.insert(id2) {
+                        worklist.push(id2);
+                    }
+                }
+            }
+        }
+
+        for &(id, ref keys) in &impls {
+            if !seen_names.contains(&id) && keys.iter().all(|k| seen_names.contains(k)) {
+                seen_names.insert(id);
+                worklist.push(id);
+            }
+        }
+        if worklist.is_empty() {
+            break;
+        }
+    }
+    seen_names
+}
+
+/// Like `link_crates`, but with the behavior controlled by `opts`.
+pub fn link_crates_with<R, W>(
+    inputs: &mut [R],
+    mut output: W,
+    opts: LinkOptions,
+) -> Result<(), LinkError>
+where R: Read + Seek + Send, W: Write {
+    let validate = !opts.partial;
+    let (indexes, json_offsets) = read_crates(inputs)?;
+    let (mut it, defs, translate) = assign_global_ids(&indexes);
+    let roots = collect_roots(&indexes, &translate);
+    let pretty = lib_util::pretty_json_enabled();
+
+    let mut crate_infos = Vec::with_capacity(inputs.len());
+    for (i, input) in inputs.iter_mut().enumerate() {
+        let info = lib_util::read_crate_info(input)
+            .map_err(|e| LinkError::Parse { input: i, source: e })?;
+        crate_infos.push(info);
+    }
+
+    let seen_names = if opts.prune {
+        // A binary's `main` isn't a root, since it isn't a test, but it and everything it uses
+        // must still be kept.
+        let mut seeds = roots.clone();
+        for info in &crate_infos {
+            if let Some(name) = info.get("entry_fn").and_then(|e| e["instance"].as_str()) {
+                seeds.push(it.intern(name.into()));
+            }
+        }
+        live_names(&indexes, &defs, &translate, seeds)
+    } else {
+        defs.keys().cloned().collect()
+    };
+
+    if validate {
+        check_calls_defined(&indexes, &it, &defs, &translate, &seen_names)?;
+    }
+
 
     // Set up the tables that will be written to the output.
-    let mut output_tables = vec![Vec::new(); 6];
+    let mut output_tables = vec![Vec::new(); 7];
     for &id in &seen_names {
-        let mut saw_entry = [false; 6];
+        // For each kind, the crate and digest of the entry we're using.
+        let mut saw_entry: [Option<(usize, Option<&str>)>; 7] = [None; 7];
         // Check each input crate that defines the item, in case it has additional entries not
         // present in other crates.
         let def_list = match defs.get(&id) {
@@ -105,11 +372,27 @@ where R: Read + Seek, W: Write {
             None => continue,
         };
         for &(crate_num, local_id) in def_list {
-            for (&kind, &(offset, len)) in &indexes[crate_num].items[&local_id].locations {
-                if saw_entry[kind as usize] {
+            let data = &indexes[crate_num].items[&local_id];
+            for (&kind, &(offset, len)) in &data.locations {
+                let digest = data.digests.get(&kind).map(|s| s as &str);
+                if let Some((first_crate, first_digest)) = saw_entry[kind as usize] {
+                    // Entries from indexes without digests can't be compared, so they're
+                    // assumed to match.
+                    let differ = match (first_digest, digest) {
+                        (Some(a), Some(b)) => a != b,
+                        _ => false,
+                    };
+                    if validate && differ {
+                        return Err(LinkError::Conflict {
+                            name: it.name(id).to_owned(),
+                            kind,
+                            a: first_crate,
+                            b: crate_num,
+                        });
+                    }
                     continue;
                 }
-                saw_entry[kind as usize] = true;
+                saw_entry[kind as usize] = Some((crate_num, digest));
 
                 let abs_offset = json_offsets[crate_num] + offset;
                 output_tables[kind as usize].push((crate_num, abs_offset, len));
@@ -138,7 +421,7 @@ where R: Read + Seek, W: Write {
         write!(output, "]")?;
     }
     write!(output, ",")?;
-    write!(output, "\"impls\":[]")?;
+    write!(output, "\"version\":{}", lib_util::SCHEMA_VERSION)?;
     write!(output, ",")?;
     write!(output, "\"roots\":[")?;
     for (i, &id) in roots.iter().enumerate() {
@@ -146,18 +429,133 @@ where R: Read + Seek, W: Write {
             write!(output, ",")?;
         }
         let name = it.name(id);
-        serde_json::to_writer(&mut output, name)
-            .map_err(|e| -> io::Error { e.into() })?;
+        lib_util::write_json(&mut output, name, pretty)?;
     }
     write!(output, "]")?;
+
+    let mut crate_info = BTreeMap::new();
+    for info in crate_infos {
+        lib_util::merge_crate_info(&mut crate_info, info);
+    }
+    for (key, val) in &crate_info {
+        write!(output, ",")?;
+        lib_util::write_json(&mut output, key, pretty)?;
+        write!(output, ":")?;
+        lib_util::write_json(&mut output, val, pretty)?;
+    }
+
     write!(output, "}}")?;
+    output.flush()?;
+
+    Ok(())
+}
+
+/// An input to `link_mir_files`.  Split crate directories (from `MIR_JSON_SPLIT`) are converted
+/// to the indexed format in memory, so the linker itself only ever sees indexed crates.
+enum MirInput {
+    File(File),
+    Split(Cursor<Vec<u8>>),
+}
+
+impl MirInput {
+    fn open(path: &Path) -> Result<MirInput, LinkFilesError> {
+        let input_error = |e| LinkFilesError::Input { path: path.to_owned(), source: e };
+        if path.is_dir() {
+            let buf = lib_util::split_crate_to_indexed(path).map_err(input_error)?;
+            Ok(MirInput::Split(Cursor::new(buf)))
+        } else {
+            let f = File::open(path).map_err(|e| input_error(e.into()))?;
+            Ok(MirInput::File(f))
+        }
+    }
+}
+
+impl Read for MirInput {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match *self {
+            MirInput::File(ref mut f) => f.read(buf),
+            MirInput::Split(ref mut c) => c.read(buf),
+        }
+    }
+}
 
+impl Seek for MirInput {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        match *self {
+            MirInput::File(ref mut f) => f.seek(pos),
+            MirInput::Split(ref mut c) => c.seek(pos),
+        }
+    }
+}
+
+/// Errors from `link_mir_files`.  Unlike `LinkError`, these name the files involved.
+#[derive(Debug)]
+pub enum LinkFilesError {
+    /// An input file or split crate directory couldn't be opened or read.
+    Input { path: PathBuf, source: serde_cbor::Error },
+    /// The output file couldn't be created or written.
+    Output { path: PathBuf, source: io::Error },
+    /// Linking failed.  `paths` are the inputs, which `source` refers to by index.
+    Link { paths: Vec<PathBuf>, source: LinkError },
+}
+
+impl fmt::Display for LinkFilesError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            LinkFilesError::Input { ref path, ref source } =>
+                write!(f, "failed to read {}: {}", path.display(), source),
+            LinkFilesError::Output { ref path, ref source } =>
+                write!(f, "failed to write {}: {}", path.display(), source),
+            LinkFilesError::Link { ref paths, ref source } =>
+                write!(f, "{}", source.describe(paths)),
+        }
+    }
+}
+
+impl Error for LinkFilesError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match *self {
+            LinkFilesError::Input { ref source, .. } => Some(source),
+            LinkFilesError::Output { ref source, .. } => Some(source),
+            LinkFilesError::Link { ref source, .. } => Some(source),
+        }
+    }
+}
+
+/// Link the crates at `paths` into a JSON file at `out_path`.  Each path is either a `.mir` file
+/// or a split crate directory, and the first one is the main crate.  If `out_path` ends in `.gz`,
+/// the output is gzip-compressed.  `opts` is passed on to `link_crates_with`.
+pub fn link_mir_files(
+    paths: &[PathBuf],
+    out_path: &Path,
+    opts: LinkOptions,
+) -> Result<(), LinkFilesError> {
+    let mut inputs = paths.iter()
+        .map(|p| MirInput::open(p))
+        .collect::<Result<Vec<_>, _>>()?;
+    let output_error = |e| LinkFilesError::Output { path: out_path.to_owned(), source: e };
+    let output = File::create(out_path).map_err(output_error)?;
+    let output = io::BufWriter::new(output);
+    let link = |inputs: &mut [MirInput], output: &mut dyn Write| {
+        link_crates_with(inputs, output, opts)
+            .map_err(|e| LinkFilesError::Link { paths: paths.to_owned(), source: e })
+    };
+
+    if out_path.extension().map_or(false, |ext| ext == "gz") {
+        let mut enc = GzEncoder::new(output, Compression::default());
+        link(&mut inputs, &mut enc)?;
+        // Dropping the encoder would also finish the stream, but would ignore any errors.
+        enc.finish().and_then(|mut w| w.flush()).map_err(output_error)?;
+    } else {
+        let mut output = output;
+        link(&mut inputs, &mut output)?;
+    }
     Ok(())
 }
 
-pub fn gather_calls<R: Read + Seek>(
+pub fn gather_calls<R: Read + Seek + Send>(
     inputs: &mut [R],
-) -> serde_cbor::Result<(InternTable, Vec<(StringId, StringId)>)> {
+) -> Result<(InternTable, Vec<(StringId, StringId)>), LinkError> {
     let (indexes, json_offsets) = read_crates(inputs)?;
     let (it, defs, translate) = assign_global_ids(&indexes);
     let roots = collect_roots(&indexes, &translate);
@@ -186,3 +584,368 @@ pub fn gather_calls<R: Read + Seek>(
 
     Ok((it, calls))
 }
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tar;
+    use crate::test_fixtures::{crate_json, fn_entry, indexed, temp_dir};
+
+    fn link(inputs: &mut [Cursor<Vec<u8>>]) -> Result<JsonValue, LinkError> {
+        let mut out = Vec::new();
+        link_crates(inputs, &mut out)?;
+        Ok(serde_json::from_slice(&out).unwrap())
+    }
+
+    fn fn_names(j: &JsonValue) -> Vec<&str> {
+        let mut names = j["fns"].as_array().unwrap().iter()
+            .map(|f| f["name"].as_str().unwrap())
+            .collect::<Vec<_>>();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn link_several_inputs() {
+        let mut main = crate_json(vec![fn_entry("m::main", json!("u8"), &["a::g"])], &["m::main"]);
+        main["unsupported_predicates"] = json!({"Subtype": 1});
+        let mut a = crate_json(vec![fn_entry("a::g", json!("u8"), &["b::h"])], &[]);
+        a["unsupported_predicates"] = json!({"Subtype": 2});
+        let b = crate_json(vec![fn_entry("b::h", json!("u8"), &[])], &[]);
+
+        let mut out = Vec::new();
+        link_crates(&mut [indexed(&main), indexed(&a), indexed(&b)], &mut out).unwrap();
+        let j: JsonValue = serde_json::from_slice(&out).unwrap();
+        assert_eq!(fn_names(&j), ["a::g", "b::h", "m::main"]);
+        assert_eq!(j["roots"], json!(["m::main"]));
+        assert_eq!(j["version"], lib_util::SCHEMA_VERSION);
+        assert_eq!(j["unsupported_predicates"], json!({"Subtype": 3}));
+    }
+
+    #[test]
+    fn link_in_memory() {
+        let f = fn_entry("m::f", json!("u8"), &["d::g"]);
+        let g = fn_entry("d::g", json!("u16"), &[]);
+        let mut inputs = vec![
+            indexed(&crate_json(vec![f.clone()], &["m::f"])),
+            indexed(&crate_json(vec![g.clone()], &[])),
+        ];
+        let mut out = Cursor::new(Vec::new());
+        link_crates(&mut inputs, &mut out).unwrap();
+
+        let j: JsonValue = serde_json::from_slice(out.get_ref()).unwrap();
+        let mut fns = j["fns"].as_array().unwrap().clone();
+        fns.sort_by_key(|f| f["name"].as_str().unwrap().to_owned());
+        assert_eq!(fns, [g, f]);
+    }
+
+    #[test]
+    fn link_many_inputs_in_parallel() {
+        const N: usize = 32;
+        let name = |i: usize| format!("c{}::f", i);
+        let mut inputs = (0 .. N).map(|i| {
+            let callees = if i + 1 < N { vec![name(i + 1)] } else { vec![] };
+            let callees = callees.iter().map(|s| s as &str).collect::<Vec<_>>();
+            let roots = if i == 0 { vec!["c0::f"] } else { vec![] };
+            indexed(&crate_json(vec![fn_entry(&name(i), json!("u8"), &callees)], &roots))
+        }).collect::<Vec<_>>();
+
+        // The parallel reader should give the same results, in the same order, as reading each
+        // input in turn.
+        let (indexes, offsets) = read_crates(&mut inputs).unwrap();
+        for (i, input) in inputs.iter_mut().enumerate() {
+            let (index, offset) = lib_util::read_crate_index(input).unwrap();
+            assert_eq!(indexes[i].names, index.names);
+            assert_eq!(indexes[i].hash, index.hash);
+            assert_eq!(offsets[i], offset);
+        }
+
+        let j = link(&mut inputs).unwrap();
+        let mut expected = (0 .. N).map(name).collect::<Vec<_>>();
+        expected.sort();
+        assert_eq!(fn_names(&j), expected);
+    }
+
+    #[test]
+    fn undefined_call_is_reported_by_name() {
+        let main = crate_json(vec![fn_entry("m::f", json!("u8"), &["d::g", "d::h"])], &["m::f"]);
+        let mut dep = crate_json(vec![], &[]);
+        dep["intrinsics"] = json!([{"name": "d::h", "inst": {"kind": "Intrinsic"}}]);
+
+        let mut inputs = [indexed(&main), indexed(&dep)];
+        let err = link(&mut inputs).unwrap_err();
+        let msg = err.to_string();
+        assert!(msg.contains("d::g (referenced by m::f)"), "{}", msg);
+        // `d::h` has an intrinsic entry, so it counts as defined.
+        assert!(!msg.contains("d::h"), "{}", msg);
+
+        // The check is skipped for partial links.
+        let mut out = Vec::new();
+        link_crates_partial(&mut inputs, &mut out).unwrap();
+    }
+
+    #[test]
+    fn unresolved_call_is_not_checked() {
+        let mut main = crate_json(vec![fn_entry("m::f", json!("u8"), &[])], &["m::f"]);
+        // `analyz` emits the generic item's path, with no instance, when resolution fails.
+        main["fns"][0]["body"]["blocks"][0]["block"]["terminator"] = json!({
+            "kind": "Call",
+            "func": {"ty": {"kind": "FnDef", "defid": "d::generic", "inst": null}},
+        });
+        let mut out = Vec::new();
+        link_crates(&mut [indexed(&main)], &mut out).unwrap();
+    }
+
+    fn link_pruned(inputs: &mut [Cursor<Vec<u8>>]) -> JsonValue {
+        let mut out = Vec::new();
+        let opts = LinkOptions { prune: true, ..LinkOptions::default() };
+        link_crates_with(inputs, &mut out, opts).unwrap();
+        serde_json::from_slice(&out).unwrap()
+    }
+
+    #[test]
+    fn prune_unreachable() {
+        let mut test = fn_entry("m::test", json!("u8"), &["m::used"]);
+        // An unsizing cast to `dyn Op` names the vtable, which names the method implementation.
+        test["body"]["blocks"][0]["block"]["data"] = json!([
+            {"kind": "Assign", "rhs": {"kind": "Cast", "vtable": "m::{{vtable}}"}},
+        ]);
+        let mut main = crate_json(vec![
+            test,
+            fn_entry("m::used", json!("u8"), &[]),
+            fn_entry("m::Doubler::apply", json!("u8"), &[]),
+            fn_entry("m::unused_helper", json!("u8"), &[]),
+        ], &["m::test"]);
+        main["vtables"] = json!([{"name": "m::{{vtable}}", "items": ["m::Doubler::apply"]}]);
+        let dep = crate_json(vec![fn_entry("d::dead", json!("u8"), &[])], &[]);
+
+        let j = link_pruned(&mut [indexed(&main), indexed(&dep)]);
+        assert_eq!(fn_names(&j), ["m::Doubler::apply", "m::test", "m::used"]);
+        assert_eq!(j["vtables"].as_array().unwrap().len(), 1);
+
+        // Without pruning, everything is kept.
+        let j = link(&mut [indexed(&main), indexed(&dep)]).unwrap();
+        assert_eq!(fn_names(&j),
+            ["d::dead", "m::Doubler::apply", "m::test", "m::unused_helper", "m::used"]);
+    }
+
+    #[test]
+    fn prune_walks_live_impls() {
+        let mut test = fn_entry("m::test", json!({"kind": "Adt", "name": "m::S"}), &[]);
+        // Casting to `dyn Debug` names the trait.
+        test["body"]["blocks"][0]["block"]["data"] = json!([
+            {"kind": "Assign", "rhs": {"kind": "Cast", "ty": {"kind": "Dynamic",
+                "predicates": [{"kind": "Trait", "trait": "m::Debug"}]}}},
+        ]);
+        let mut main = crate_json(vec![
+            test,
+            fn_entry("m::S::fmt", json!("u8"), &["m::fmt_helper"]),
+            fn_entry("m::fmt_helper", json!("u8"), &[]),
+            fn_entry("m::Wrapper::fmt", json!("u8"), &[]),
+            fn_entry("m::Unused::fmt", json!("u8"), &[]),
+        ], &["m::test"]);
+        main["adts"] = json!([
+            {"name": "m::S", "kind": "Struct", "variants": []},
+            {"name": "m::Wrapper", "kind": "Struct", "variants": []},
+            {"name": "m::Unused", "kind": "Struct", "variants": []},
+        ]);
+        main["traits"] = json!([{"name": "m::Debug", "items": []}]);
+        // `impl Debug for S` mentions `Wrapper` in its signature, so once it's live, so is
+        // `impl Debug for Wrapper`.
+        main["impls"] = json!([
+            {"name": "m::{{impl}}[0]", "trait": "m::Debug", "self_adt": "m::S",
+                "items": ["m::S::fmt"], "predicates": ["m::Debug", "m::Wrapper"]},
+            {"name": "m::{{impl}}[1]", "trait": "m::Debug", "self_adt": "m::Wrapper",
+                "items": ["m::Wrapper::fmt"]},
+            {"name": "m::{{impl}}[2]", "trait": "m::Debug", "self_adt": "m::Unused",
+                "items": ["m::Unused::fmt"]},
+        ]);
+
+        let j = link_pruned(&mut [indexed(&main)]);
+        assert_eq!(fn_names(&j), ["m::S::fmt", "m::Wrapper::fmt", "m::fmt_helper", "m::test"]);
+        assert_eq!(j["impls"].as_array().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn prune_keeps_entry_fn() {
+        let mut bin = crate_json(vec![
+            fn_entry("b::main", json!("u8"), &["b::helper"]),
+            fn_entry("b::helper", json!("u8"), &[]),
+            fn_entry("b::dead", json!("u8"), &[]),
+        ], &[]);
+        bin["entry_fn"] = json!({"def_id": "b::main", "instance": "b::main", "kind": "Main"});
+
+        let j = link_pruned(&mut [indexed(&bin)]);
+        assert_eq!(fn_names(&j), ["b::helper", "b::main"]);
+        // `main` is kept, but it isn't a test root.
+        assert_eq!(j["roots"], json!([]));
+    }
+
+    #[test]
+    fn link_files() {
+        use flate2::read::GzDecoder;
+
+        let dir = temp_dir("link-files");
+        let main = crate_json(vec![fn_entry("m::f", json!("u8"), &["d::g"])], &["m::f"]);
+        let dep = crate_json(vec![fn_entry("d::g", json!("u8"), &[])], &[]);
+        let paths = vec![dir.join("main.mir"), dir.join("dep.mir")];
+        for (path, j) in paths.iter().zip(&[main, dep]) {
+            lib_util::write_indexed_crate(File::create(path).unwrap(), j).unwrap();
+        }
+
+        let out_path = dir.join("out.json");
+        link_mir_files(&paths, &out_path, LinkOptions::default()).unwrap();
+        let plain = std::fs::read(&out_path).unwrap();
+        let j: JsonValue = serde_json::from_slice(&plain).unwrap();
+        assert_eq!(fn_names(&j), ["d::g", "m::f"]);
+
+        let gz_path = dir.join("out.json.gz");
+        link_mir_files(&paths, &gz_path, LinkOptions::default()).unwrap();
+        let mut unzipped = Vec::new();
+        GzDecoder::new(File::open(&gz_path).unwrap()).read_to_end(&mut unzipped).unwrap();
+        assert_eq!(unzipped, plain);
+
+        // Errors name the file involved.
+        let missing = vec![paths[0].clone(), dir.join("missing.mir")];
+        match link_mir_files(&missing, &out_path, LinkOptions::default()) {
+            Err(LinkFilesError::Input { ref path, .. }) if path == &missing[1] => {},
+            r => panic!("expected Input error for missing.mir, got {:?}", r),
+        }
+        match link_mir_files(&paths[.. 1], &out_path, LinkOptions::default()) {
+            Err(ref e @ LinkFilesError::Link { .. }) => {
+                match e.source().and_then(|e| e.downcast_ref::<LinkError>()) {
+                    Some(&LinkError::MissingDefinition { .. }) => {},
+                    r => panic!("expected MissingDefinition, got {:?}", r),
+                }
+            },
+            r => panic!("expected Link error, got {:?}", r),
+        }
+        let bad_out = dir.join("no-such-dir").join("out.json");
+        match link_mir_files(&paths, &bad_out, LinkOptions::default()) {
+            Err(LinkFilesError::Output { ref path, .. }) if path == &bad_out => {},
+            r => panic!("expected Output error, got {:?}", r),
+        }
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    struct FailingWriter;
+
+    impl Write for FailingWriter {
+        fn write(&mut self, _: &[u8]) -> io::Result<usize> {
+            Err(io::Error::new(io::ErrorKind::Other, "disk full"))
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn io_error() {
+        let mut inputs = [indexed(&crate_json(vec![fn_entry("c::f", json!("u8"), &[])],
+            &["c::f"]))];
+        match link_crates(&mut inputs, FailingWriter) {
+            Err(LinkError::Io(_)) => {},
+            r => panic!("expected Io, got {:?}", r),
+        }
+    }
+
+    #[test]
+    fn parse_error() {
+        let good = indexed(&crate_json(vec![fn_entry("c::f", json!("u8"), &[])], &["c::f"]));
+        let mut inputs = [good, Cursor::new(vec![b'x'; 1024])];
+        match link(&mut inputs) {
+            Err(LinkError::Parse { input: 1, .. }) => {},
+            r => panic!("expected Parse for input 1, got {:?}", r),
+        }
+    }
+
+    #[test]
+    fn conflict() {
+        let a = crate_json(vec![fn_entry("c::f", json!("u8"), &[])], &["c::f"]);
+        let b = crate_json(vec![fn_entry("c::f", json!("u16"), &[])], &[]);
+        let mut inputs = [indexed(&a), indexed(&b)];
+        match link(&mut inputs) {
+            Err(LinkError::Conflict { ref name, kind: EntryKind::Fn, a: 0, b: 1 })
+                if name == "c::f" => {},
+            r => panic!("expected Conflict on c::f, got {:?}", r),
+        }
+
+        // The partial linker keeps the first input's entry instead.
+        let mut out = Vec::new();
+        link_crates_partial(&mut inputs, &mut out).unwrap();
+        let j: JsonValue = serde_json::from_slice(&out).unwrap();
+        assert_eq!(j["fns"][0]["return_ty"], "u8");
+    }
+
+    #[test]
+    fn crate_dependent_fields_dont_conflict() {
+        let ty_a = json!({"kind": "Closure", "upvar_modes": ["ByValue"], "pretty": "[closure]"});
+        let ty_b = json!({"kind": "Closure", "upvar_modes": null, "pretty": "c::[closure]"});
+        let a = crate_json(vec![fn_entry("c::f", ty_a, &[])], &["c::f"]);
+        let b = crate_json(vec![fn_entry("c::f", ty_b, &[])], &[]);
+        let j = link(&mut [indexed(&a), indexed(&b)]).unwrap();
+        assert_eq!(fn_names(&j), ["c::f"]);
+    }
+
+    /// Build an empty indexed crate with a hand-written `index.cbor`.
+    fn raw_crate(index_buf: Vec<u8>) -> Cursor<Vec<u8>> {
+        let mut tar = tar::Builder::new(Vec::new());
+        for &(name, ref buf) in &[("crate.json", b"{}".to_vec()), ("index.cbor", index_buf)] {
+            let mut hdr = tar::Header::new_ustar();
+            hdr.set_size(buf.len() as u64);
+            hdr.set_mode(0o644);
+            tar.append_data(&mut hdr, name, &buf[..]).unwrap();
+        }
+        Cursor::new(tar.into_inner().unwrap())
+    }
+
+    #[test]
+    fn version_mismatch() {
+        let index = CrateIndex { version: lib_util::SCHEMA_VERSION - 1, ..Default::default() };
+        let old = raw_crate(serde_cbor::to_vec(&index).unwrap());
+
+        let good = indexed(&crate_json(vec![fn_entry("c::f", json!("u8"), &[])], &["c::f"]));
+        match link(&mut [good, old]) {
+            Err(LinkError::VersionMismatch { input: 1, found, expected }) => {
+                assert_eq!(found, lib_util::SCHEMA_VERSION - 1);
+                assert_eq!(expected, lib_util::SCHEMA_VERSION);
+            },
+            r => panic!("expected VersionMismatch for input 1, got {:?}", r),
+        }
+    }
+
+    #[test]
+    fn unversioned_input_is_rejected() {
+        // Indexes written before versioning have no `version` key at all.
+        let mut index = BTreeMap::new();
+        index.insert("names", CborValue::Array(vec![]));
+        index.insert("items", CborValue::Map(BTreeMap::new()));
+        index.insert("roots", CborValue::Array(vec![]));
+        let old = raw_crate(serde_cbor::to_vec(&index).unwrap());
+
+        let good = indexed(&crate_json(vec![fn_entry("c::f", json!("u8"), &[])], &["c::f"]));
+        let mut inputs = [old, good];
+        let paths = [PathBuf::from("old.mir"), PathBuf::from("new.mir")];
+        let mut out = Vec::new();
+        match link_crates_partial(&mut inputs, &mut out) {
+            Err(ref e @ LinkError::VersionMismatch { input: 0, found: 0, .. }) => {
+                assert!(e.describe(&paths).starts_with("old.mir has schema version 0"));
+            },
+            r => panic!("expected VersionMismatch for input 0, got {:?}", r),
+        }
+        assert!(out.is_empty());
+    }
+
+    #[test]
+    fn missing_definition() {
+        let j = crate_json(vec![fn_entry("c::f", json!("u8"), &["c::g"])], &["c::f"]);
+        match link(&mut [indexed(&j)]) {
+            Err(LinkError::MissingDefinition { ref missing })
+                if missing == &[("c::g".to_owned(), "c::f".to_owned())] => {},
+            r => panic!("expected MissingDefinition for c::g, got {:?}", r),
+        }
+    }
+}
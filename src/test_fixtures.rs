@@ -0,0 +1,58 @@
+//! Small crates and helpers shared by the unit tests and the integration tests in `tests/`.  This
+//! isn't part of the public API.
+use std::env;
+use std::fs;
+use std::io::Cursor;
+use std::path::PathBuf;
+use std::process;
+
+use serde_json::Value as JsonValue;
+
+use crate::lib_util;
+
+/// A crate in the form `write_indexed_crate` takes, with the given `fns` and `roots` and every
+/// other table empty.
+pub fn crate_json(fns: Vec<JsonValue>, roots: &[&str]) -> JsonValue {
+    json!({
+        "fns": fns,
+        "adts": [],
+        "statics": [],
+        "vtables": [],
+        "traits": [],
+        "intrinsics": [],
+        "impls": [],
+        "roots": roots,
+    })
+}
+
+/// A function entry named `name`, returning `ret`, whose body calls each of `callees`.
+pub fn fn_entry(name: &str, ret: JsonValue, callees: &[&str]) -> JsonValue {
+    let calls = callees.iter()
+        .map(|c| json!({"kind": "Call", "func": {"ty": {"kind": "FnDef", "defid": c}}}))
+        .collect::<Vec<_>>();
+    json!({
+        "name": name,
+        "args": [],
+        "return_ty": ret,
+        "body": {"vars": [], "blocks": [{"blockid": "bb0", "block": {
+            "data": [],
+            "terminator": calls.get(0).cloned().unwrap_or(json!({"kind": "Return"})),
+            "calls": calls,
+        }}]},
+        "promoted": [],
+    })
+}
+
+/// The indexed `.mir` form of `j`, ready to pass to the linker.
+pub fn indexed(j: &JsonValue) -> Cursor<Vec<u8>> {
+    let (buf, _) = lib_util::build_indexed_crate(Vec::new(), j).unwrap();
+    Cursor::new(buf)
+}
+
+/// A fresh, empty directory for a test's files.  `name` must be unique among the tests.
+pub fn temp_dir(name: &str) -> PathBuf {
+    let dir = env::temp_dir().join(format!("mir-json-{}-{}", name, process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
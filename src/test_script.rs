@@ -0,0 +1,105 @@
+//! Test scripts that run `crux-mir` on a linked JSON file.  These stand in for the test
+//! executable that `cargo test` would normally run.
+use std::env;
+use std::fs::OpenOptions;
+use std::io::{self, Write};
+#[cfg(unix)]
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::Path;
+
+use shell_words;
+
+/// Build the `crux-mir` command line used by test scripts.  `CRUX_MIR` overrides the path to the
+/// `crux-mir` binary, and `CRUX_MIR_ARGS` gives extra arguments, split using shell word rules.
+/// Returns an `InvalidInput` error if `CRUX_MIR_ARGS` can't be split.
+pub fn crux_mir_command() -> io::Result<Vec<String>> {
+    let mut cmd = vec![
+        env::var("CRUX_MIR").unwrap_or_else(|_| "crux-mir".into()),
+        "--assert-false-on-error".into(),
+    ];
+    if let Ok(s) = env::var("CRUX_MIR_ARGS") {
+        cmd.extend(split_crux_mir_args(&s)?);
+    }
+    Ok(cmd)
+}
+
+fn split_crux_mir_args(s: &str) -> io::Result<Vec<String>> {
+    shell_words::split(s).map_err(|e| io::Error::new(
+        io::ErrorKind::InvalidInput,
+        format!("failed to parse CRUX_MIR_ARGS {:?}: {}", s, e),
+    ))
+}
+
+/// Write an executable script at `script_path` that runs `crux-mir` on `json_path`.  The JSON
+/// file is located relative to the script, so both must live in the same directory.
+#[cfg(unix)]
+pub fn write_test_script(script_path: &Path, json_path: &Path) -> io::Result<()> {
+    let json_name = json_path.file_name().unwrap().to_str().unwrap();
+    let cmd = crux_mir_command()?.iter()
+        .map(|s| shell_words::quote(s).into_owned())
+        .collect::<Vec<_>>()
+        .join(" ");
+    let mut f = OpenOptions::new().write(true).create(true).truncate(true)
+        .mode(0o755).open(script_path)?;
+    writeln!(f, "#!/bin/sh")?;
+    writeln!(f, r#"exec {} "$(dirname "$0")"/{}"#, cmd, shell_words::quote(json_name))?;
+    Ok(())
+}
+
+/// On Windows, cargo expects the test binary to be a `.exe`, which we can't easily produce, so we
+/// write a batch file next to it instead.  `%~dp0` expands to the directory containing the script.
+#[cfg(not(unix))]
+pub fn write_test_script(script_path: &Path, json_path: &Path) -> io::Result<()> {
+    let json_name = json_path.file_name().unwrap().to_str().unwrap();
+    let cmd = crux_mir_command()?.iter()
+        .map(|s| format!("\"{}\"", s))
+        .collect::<Vec<_>>()
+        .join(" ");
+    let script_path = script_path.with_extension("bat");
+    let mut f = OpenOptions::new().write(true).create(true).truncate(true)
+        .open(&script_path)?;
+    writeln!(f, "@echo off")?;
+    writeln!(f, r#"{} "%~dp0{}" %*"#, cmd, json_name)?;
+    writeln!(f, "exit /b %ERRORLEVEL%")?;
+    Ok(())
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use crate::test_fixtures::temp_dir;
+
+    #[test]
+    fn split_args() {
+        assert_eq!(split_crux_mir_args("--solver z3 'a b'").unwrap(), ["--solver", "z3", "a b"]);
+        let e = split_crux_mir_args("--solver 'z3").unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::InvalidInput);
+        assert!(e.to_string().contains("CRUX_MIR_ARGS"), "{}", e);
+    }
+
+    // Other tests write scripts concurrently, so this only sets well-formed values.
+    #[cfg(unix)]
+    #[test]
+    fn script_uses_env_command() {
+        let dir = temp_dir("test-script");
+        let script_path = dir.join("test");
+
+        env::set_var("CRUX_MIR", "/opt/crux mir/bin/crux-mir");
+        env::set_var("CRUX_MIR_ARGS", "--solver z3 '--path-sat'");
+        let r = write_test_script(&script_path, &dir.join("test.linked-mir.json"));
+        env::remove_var("CRUX_MIR");
+        env::remove_var("CRUX_MIR_ARGS");
+        r.unwrap();
+
+        let script = fs::read_to_string(&script_path).unwrap();
+        assert_eq!(script, concat!(
+            "#!/bin/sh\n",
+            "exec '/opt/crux mir/bin/crux-mir' --assert-false-on-error --solver z3 --path-sat ",
+            r#""$(dirname "$0")"/test.linked-mir.json"#, "\n",
+        ));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,286 @@
+//! Structural checks on the JSON produced by `analyz`.
+//!
+//! This is a debugging aid, enabled with `MIR_JSON_VALIDATE`, for catching serializers that emit
+//! malformed or incomplete objects.  It doesn't check the full schema, only the parts that
+//! consumers rely on to navigate the output: the top-level keys of each entry, the shape of MIR
+//! bodies, and the presence of a `kind` on every statement, terminator, and type.
+
+use std::fmt::Write as FmtWrite;
+use std::io;
+use serde_json::Value as JsonValue;
+
+use crate::lib_util::{EntryKind, JsonOutput};
+
+/// Keys that must be present (though possibly `null`) in every entry of each kind.
+fn required_keys(kind: EntryKind) -> &'static [&'static str] {
+    match kind {
+        EntryKind::Fn => &["name", "args", "return_ty", "body", "promoted"],
+        EntryKind::Adt => &["name", "kind", "variants"],
+        EntryKind::Static => &["name", "ty", "mutable"],
+        EntryKind::Vtable => &["name", "self_ty", "trait", "items"],
+        EntryKind::Trait => &["name", "items"],
+        EntryKind::Intrinsic => &["name", "inst"],
+        EntryKind::Impl => &["name", "self_ty", "items"],
+    }
+}
+
+/// Keys whose values are types, or arrays of types.
+const TYPE_KEYS: &[&str] = &["ty", "return_ty", "self_ty"];
+const TYPE_LIST_KEYS: &[&str] = &["tys"];
+
+/// A validation failure: the path to the offending value (in JSON Pointer syntax, relative to the
+/// entry) and a description of the problem.
+#[derive(Debug)]
+pub struct ValidationError {
+    pub path: String,
+    pub msg: String,
+}
+
+struct Validator {
+    path: Vec<String>,
+    errors: Vec<ValidationError>,
+}
+
+impl Validator {
+    fn error(&mut self, msg: impl Into<String>) {
+        let mut path = String::new();
+        for p in &self.path {
+            write!(path, "/{}", p).unwrap();
+        }
+        self.errors.push(ValidationError { path, msg: msg.into() });
+    }
+
+    fn nested<F: FnOnce(&mut Self)>(&mut self, key: impl ToString, f: F) {
+        self.path.push(key.to_string());
+        f(self);
+        self.path.pop();
+    }
+
+    fn require_keys(&mut self, j: &JsonValue, keys: &[&str]) {
+        let obj = match j.as_object() {
+            Some(x) => x,
+            None => return self.error("expected an object"),
+        };
+        for &k in keys {
+            if !obj.contains_key(k) {
+                self.error(format!("missing required key {:?}", k));
+            }
+        }
+    }
+
+    fn require_array<'a>(&mut self, j: &'a JsonValue, key: &str) -> &'a [JsonValue] {
+        match j.get(key).and_then(|x| x.as_array()) {
+            Some(x) => x,
+            None => {
+                self.error(format!("expected {:?} to be an array", key));
+                &[]
+            },
+        }
+    }
+
+    fn require_kind(&mut self, j: &JsonValue) {
+        match j.get("kind") {
+            Some(&JsonValue::String(_)) => {},
+            Some(_) => self.error("\"kind\" is not a string"),
+            None => self.error("missing \"kind\""),
+        }
+    }
+
+    fn body(&mut self, body: &JsonValue) {
+        self.require_keys(body, &["vars", "blocks"]);
+        for (i, var) in self.require_array(body, "vars").iter().enumerate() {
+            self.nested("vars", |v| v.nested(i, |v| v.require_keys(var, &["name", "ty"])));
+        }
+        for (i, bb) in self.require_array(body, "blocks").iter().enumerate() {
+            self.nested("blocks", |v| v.nested(i, |v| {
+                v.require_keys(bb, &["blockid", "block"]);
+                let block = match bb.get("block") {
+                    Some(x) => x,
+                    None => return,
+                };
+                v.nested("block", |v| {
+                    v.require_keys(block, &["data", "terminator"]);
+                    for (j, stmt) in v.require_array(block, "data").iter().enumerate() {
+                        v.nested("data", |v| v.nested(j, |v| v.require_kind(stmt)));
+                    }
+                    if let Some(term) = block.get("terminator") {
+                        v.nested("terminator", |v| v.require_kind(term));
+                    }
+                });
+            }));
+        }
+    }
+
+    /// Check every type in `j`, recursively.  Types appear under a handful of well-known keys
+    /// throughout the output, and each must be `null` or an object with a `kind`.
+    fn types(&mut self, j: &JsonValue) {
+        match *j {
+            JsonValue::Object(ref obj) => {
+                for (k, v) in obj {
+                    self.nested(k, |this| {
+                        if TYPE_KEYS.contains(&k.as_str()) && !v.is_null() {
+                            this.require_kind(v);
+                        }
+                        if TYPE_LIST_KEYS.contains(&k.as_str()) {
+                            if let Some(tys) = v.as_array() {
+                                for (i, ty) in tys.iter().enumerate() {
+                                    this.nested(i, |this| this.require_kind(ty));
+                                }
+                            }
+                        }
+                        this.types(v);
+                    });
+                }
+            },
+            JsonValue::Array(ref arr) => {
+                for (i, v) in arr.iter().enumerate() {
+                    self.nested(i, |this| this.types(v));
+                }
+            },
+            _ => {},
+        }
+    }
+}
+
+/// Check the structure of a single entry of kind `kind`.  Returns every problem found.
+pub fn validate_entry(kind: EntryKind, j: &JsonValue) -> Result<(), Vec<ValidationError>> {
+    let mut v = Validator { path: Vec::new(), errors: Vec::new() };
+    v.require_keys(j, required_keys(kind));
+    if kind == EntryKind::Fn {
+        if let Some(body) = j.get("body") {
+            v.nested("body", |v| v.body(body));
+        }
+    }
+    v.types(j);
+    if v.errors.is_empty() {
+        Ok(())
+    } else {
+        Err(v.errors)
+    }
+}
+
+/// `JsonOutput` adapter that validates each entry with `validate_entry` before passing it on.  If
+/// validation fails, `emit` returns an `InvalidData` error listing the offending paths.  The check
+/// is skipped when `enabled` is false.
+pub struct Validate<O> {
+    pub inner: O,
+    pub enabled: bool,
+}
+
+impl<O: JsonOutput> JsonOutput for Validate<O> {
+    fn emit(&mut self, kind: EntryKind, j: JsonValue) -> io::Result<()> {
+        if self.enabled {
+            if let Err(errs) = validate_entry(kind, &j) {
+                let mut msg = format!("{:?} entry {} is malformed:", kind, j["name"]);
+                for e in errs {
+                    write!(msg, "\n  {}: {}", e.path, e.msg).unwrap();
+                }
+                return Err(io::Error::new(io::ErrorKind::InvalidData, msg));
+            }
+        }
+        self.inner.emit(kind, j)
+    }
+
+    fn add_root(&mut self, name: String) -> io::Result<()> {
+        self.inner.add_root(name)
+    }
+
+    fn add_crate_info(&mut self, key: &str, j: JsonValue) -> io::Result<()> {
+        self.inner.add_crate_info(key, j)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lib_util::Output;
+
+    fn paths(r: Result<(), Vec<ValidationError>>) -> Vec<String> {
+        r.unwrap_err().into_iter().map(|e| e.path).collect()
+    }
+
+    fn good_fn() -> JsonValue {
+        json!({
+            "name": "c::f",
+            "args": [{"name": "_1", "ty": {"kind": "Bool"}}],
+            "return_ty": {"kind": "Tuple", "tys": []},
+            "body": {
+                "vars": [{"name": "_0", "ty": {"kind": "Tuple", "tys": []}}],
+                "blocks": [{"blockid": "bb0", "block": {
+                    "data": [{"kind": "Nop"}],
+                    "terminator": {"kind": "Return"},
+                }}],
+            },
+            "promoted": [],
+        })
+    }
+
+    #[test]
+    fn accepts_well_formed_entries() {
+        validate_entry(EntryKind::Fn, &good_fn()).unwrap();
+        let st = json!({"name": "c::S", "ty": {"kind": "Uint"}, "mutable": false});
+        validate_entry(EntryKind::Static, &st).unwrap();
+    }
+
+    #[test]
+    fn missing_required_keys() {
+        let errs = validate_entry(EntryKind::Adt, &json!({"name": "c::S"})).unwrap_err();
+        let msgs = errs.iter().map(|e| &e.msg as &str).collect::<Vec<_>>();
+        assert_eq!(msgs, ["missing required key \"kind\"", "missing required key \"variants\""]);
+        assert!(errs.iter().all(|e| e.path == ""));
+
+        let errs = validate_entry(EntryKind::Trait, &json!(["c::T"])).unwrap_err();
+        assert_eq!(errs[0].msg, "expected an object");
+    }
+
+    #[test]
+    fn bad_body_shapes() {
+        let mut f = good_fn();
+        f["body"]["blocks"][0]["block"]["data"][0] = json!({"span": "x"});
+        f["body"]["blocks"][0]["block"]["terminator"]["kind"] = json!(3);
+        f["body"]["vars"][0] = json!({"name": "_0"});
+        assert_eq!(paths(validate_entry(EntryKind::Fn, &f)), [
+            "/body/vars/0",
+            "/body/blocks/0/block/data/0",
+            "/body/blocks/0/block/terminator",
+        ]);
+
+        let mut f = good_fn();
+        f["body"]["blocks"] = json!({});
+        let errs = validate_entry(EntryKind::Fn, &f).unwrap_err();
+        assert_eq!(errs[0].msg, "expected \"blocks\" to be an array");
+    }
+
+    #[test]
+    fn bad_type_keys() {
+        let mut f = good_fn();
+        f["return_ty"] = json!("()");
+        f["args"][0]["ty"] = json!({"name": "bool"});
+        f["body"]["vars"][0]["ty"]["tys"] = json!([{"kind": "Bool"}, 7]);
+        assert_eq!(paths(validate_entry(EntryKind::Fn, &f)), [
+            "/args/0/ty",
+            "/body/vars/0/ty/tys/1",
+            "/return_ty",
+        ]);
+
+        // `null` is allowed where a type is optional.
+        let mut f = good_fn();
+        f["return_ty"] = JsonValue::Null;
+        validate_entry(EntryKind::Fn, &f).unwrap();
+    }
+
+    #[test]
+    fn adapter_returns_error() {
+        let mut out = Validate { inner: Output::default(), enabled: true };
+        out.emit(EntryKind::Fn, good_fn()).unwrap();
+        let err = out.emit(EntryKind::Adt, json!({"name": "c::S"})).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert!(err.to_string().contains("missing required key \"kind\""), "{}", err);
+        assert_eq!(out.inner.fns.len(), 1);
+        assert_eq!(out.inner.adts.len(), 0);
+
+        let mut out = Validate { inner: Output::default(), enabled: false };
+        out.emit(EntryKind::Adt, json!({"name": "c::S"})).unwrap();
+    }
+}
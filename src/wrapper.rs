@@ -0,0 +1,361 @@
+//! Parts of `mir-json-rustc-wrapper` that don't need the compiler.  These live in the library,
+//! rather than in the binary, so they can be tested.
+
+use std::env;
+use std::ffi::OsString;
+use std::fs;
+use std::io;
+use std::iter;
+use std::path::{Path, PathBuf};
+
+use sha2::{Digest, Sha256};
+
+use crate::link::{self, LinkFilesError, LinkOptions};
+use crate::test_script::write_test_script;
+
+/// Get the value of a rustc flag, given either as `--flag value` or `--flag=value`.
+pub fn arg_value<'a>(args: &'a [String], flag: &str) -> Option<&'a str> {
+    let mut it = args.iter();
+    while let Some(arg) = it.next() {
+        if arg == flag {
+            return it.next().map(|s| s as &str);
+        }
+        if arg.starts_with(flag) && arg[flag.len()..].starts_with('=') {
+            return Some(&arg[flag.len() + 1 ..]);
+        }
+    }
+    None
+}
+
+/// Check whether `args` (including the `rustc` path in `args[0]`) describe a job that builds
+/// something to run on the host, like a `build.rs` script or a proc macro, rather than part of the
+/// program under test.  `primary_package` is whether cargo set `CARGO_PRIMARY_PACKAGE` for the
+/// job.
+pub fn is_host_build(args: &[String], primary_package: bool) -> bool {
+    // Build scripts are always compiled as crates named `build_script_*`, and proc macros are
+    // compiled for the host even in cross-compilation mode.
+    if arg_value(args, "--crate-name").map_or(false, |s| s.starts_with("build_script_")) {
+        return true;
+    }
+    if arg_value(args, "--crate-type") == Some("proc-macro") {
+        return true;
+    }
+    // Cargo sets `CARGO_PRIMARY_PACKAGE` for the packages the user asked to build, which are never
+    // host-only dependencies.
+    if primary_package {
+        return false;
+    }
+
+    // Fallback for dependencies of build scripts: we pass `--target x86_64-unknown-linux-gnu` to
+    // `cargo`.  This makes cargo use cross-compilation mode, even though the host and target
+    // triples are the same.  In that mode, it passes the provided `--target` through to target
+    // jobs, and omits `--target` for host jobs.
+    arg_value(args, "--target").is_none()
+}
+
+
+/// Environment variables left out of the MIR cache key.  These either change between otherwise
+/// identical builds (like cargo's jobserver settings), or only affect how the wrapper uses the
+/// cache and links the output, not the exported MIR.
+const CACHE_KEY_IGNORED_ENV: &[&str] = &[
+    "CARGO_MAKEFLAGS", "MAKEFLAGS", "MFLAGS", "OLDPWD", "SHLVL", "_",
+    "MIR_JSON_CACHE_DIR", "MIR_JSON_NO_CACHE", "MIR_JSON_PARTIAL_LINK", "MIR_JSON_GZIP",
+    "MIR_JSON_NO_LINK", "MIR_JSON_PRUNE",
+];
+
+fn hash_str(h: &mut Sha256, s: &str) {
+    h.input(s.as_bytes());
+    h.input(&[0]);
+}
+
+/// Compute the MIR cache key for a build.  This is a SHA-256 hash of everything the exported MIR
+/// can depend on:
+///
+///  * `args`, the compiler arguments, which include the `--cfg`s and the MIR optimization level.
+///  * `env_vars`, the environment, except for `CACHE_KEY_IGNORED_ENV`.  `env!` and `option_env!`
+///    can read any variable, `OUT_DIR` among them.
+///  * The contents of each of `files`.  These should be every source file the compiler read,
+///    including those loaded by `include!` and `include_str!`, and each dependency's metadata and
+///    `.mir` file.  Missing files are hashed as missing, so creating one changes the key.
+///  * The contents of `exe`, the `mir-json` binary, so that upgrading it invalidates the cache.
+pub fn mir_cache_key<I>(
+    args: &[String],
+    env_vars: I,
+    files: &[PathBuf],
+    exe: &Path,
+) -> io::Result<String>
+where I: IntoIterator<Item = (OsString, OsString)> {
+    let mut h = Sha256::new();
+
+    hash_str(&mut h, "args");
+    for arg in args {
+        hash_str(&mut h, arg);
+    }
+
+    hash_str(&mut h, "env");
+    let mut env_vars = env_vars.into_iter()
+        .map(|(k, v)| (k.to_string_lossy().into_owned(), v.to_string_lossy().into_owned()))
+        .filter(|&(ref k, _)| !CACHE_KEY_IGNORED_ENV.contains(&(k as &str)))
+        .collect::<Vec<_>>();
+    env_vars.sort();
+    for (k, v) in env_vars {
+        hash_str(&mut h, &k);
+        hash_str(&mut h, &v);
+    }
+
+    hash_str(&mut h, "files");
+    let mut files = files.to_owned();
+    files.sort();
+    files.dedup();
+    for path in &files {
+        hash_str(&mut h, &path.to_string_lossy());
+        match fs::read(path) {
+            Ok(buf) => {
+                h.input(&(buf.len() as u64).to_le_bytes());
+                h.input(&buf);
+            },
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => hash_str(&mut h, "missing"),
+            Err(e) => return Err(e),
+        }
+    }
+
+    hash_str(&mut h, "exe");
+    h.input(&fs::read(exe)?);
+
+    let hash = h.result().iter().map(|b| format!("{:02x}", b)).collect::<String>();
+    Ok(format!("{}.mir", hash))
+}
+
+/// The MIR cache lives in `$MIR_JSON_CACHE_DIR`, or by default in a `mir-json-cache` directory
+/// next to cargo's `deps` directory (for example, `target/x86_64-unknown-linux-gnu/debug/`).
+pub fn cache_dir(mir_path: &Path) -> PathBuf {
+    if let Ok(s) = env::var("MIR_JSON_CACHE_DIR") {
+        return PathBuf::from(s);
+    }
+    let deps_dir = mir_path.parent().unwrap();
+    deps_dir.parent().unwrap_or(deps_dir).join("mir-json-cache")
+}
+
+/// Copy the `.mir` file cached under `key` in `cache_dir` to `mir_path`.  Returns `false` if there
+/// is no such file.
+pub fn restore_cached_mir(cache_dir: &Path, key: &str, mir_path: &Path) -> io::Result<bool> {
+    let cache_path = cache_dir.join(key);
+    if !cache_path.is_file() {
+        return Ok(false);
+    }
+    fs::copy(&cache_path, mir_path)?;
+    Ok(true)
+}
+
+/// Store the `.mir` file at `mir_path` in `cache_dir` under `key`.
+pub fn store_cached_mir(cache_dir: &Path, key: &str, mir_path: &Path) -> io::Result<()> {
+    fs::create_dir_all(cache_dir)?;
+    fs::copy(mir_path, cache_dir.join(key))?;
+    Ok(())
+}
+
+
+/// Options for `link_test_build`.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct TestBuildOptions {
+    /// Gzip-compress the linked JSON.
+    pub gzip: bool,
+    /// Stop before linking, and only report what would have been linked.  This lets export
+    /// failures be diagnosed separately from link failures.
+    pub no_link: bool,
+    pub link: LinkOptions,
+}
+
+impl TestBuildOptions {
+    /// Read the options from the environment: `MIR_JSON_GZIP` sets `gzip`, `MIR_JSON_NO_LINK`
+    /// sets `no_link`, and `link` comes from `LinkOptions::from_env`.
+    pub fn from_env() -> TestBuildOptions {
+        TestBuildOptions {
+            gzip: env::var("MIR_JSON_GZIP").is_ok(),
+            no_link: env::var("MIR_JSON_NO_LINK").is_ok(),
+            link: LinkOptions::from_env(),
+        }
+    }
+}
+
+/// Finish a `--test` build, once the crate's MIR has been written to `mir_path`.  This links it
+/// with `extern_paths` into a JSON file next to `test_path`, and writes a script in place of the
+/// test executable at `test_path` that runs `crux-mir` on the JSON.  Returns the path of the
+/// linked JSON, or `None` if `opts.no_link` is set, in which case nothing is written.
+pub fn link_test_build(
+    mir_path: &Path,
+    extern_paths: &[PathBuf],
+    test_path: &Path,
+    opts: TestBuildOptions,
+) -> Result<Option<PathBuf>, LinkFilesError> {
+    let json_path = if opts.gzip {
+        test_path.with_extension(".linked-mir.json.gz")
+    } else {
+        test_path.with_extension(".linked-mir.json")
+    };
+    eprintln!("{} {} mir files into {}",
+        if opts.no_link { "MIR_JSON_NO_LINK is set - would link" } else { "linking" },
+        1 + extern_paths.len(), json_path.display());
+    eprintln!(
+        "  inputs: {}{}",
+        mir_path.display(),
+        extern_paths.iter().map(|x| format!(" {}", x.display())).collect::<String>(),
+    );
+    if opts.no_link {
+        return Ok(None);
+    }
+
+    let paths = iter::once(mir_path.to_owned())
+        .chain(extern_paths.iter().cloned())
+        .collect::<Vec<_>>();
+    link::link_mir_files(&paths, &json_path, opts.link)?;
+
+    write_test_script(test_path, &json_path)
+        .map_err(|e| LinkFilesError::Output { path: test_path.to_owned(), source: e })?;
+    eprintln!("generated test script {}", test_path.display());
+    Ok(Some(json_path))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::File;
+    use crate::lib_util;
+    use crate::test_fixtures::{crate_json, fn_entry, temp_dir};
+
+    fn args(s: &str) -> Vec<String> {
+        s.split_whitespace().map(|s| s.to_owned()).collect()
+    }
+
+    #[test]
+    fn arg_value_forms() {
+        let a = args("rustc --crate-name foo --edition=2018 src/lib.rs --crate-type");
+        assert_eq!(arg_value(&a, "--crate-name"), Some("foo"));
+        assert_eq!(arg_value(&a, "--edition"), Some("2018"));
+        // A flag at the end has no value.
+        assert_eq!(arg_value(&a, "--crate-type"), None);
+        assert_eq!(arg_value(&a, "--target"), None);
+        // `--crate` is a prefix of `--crate-name`, but not the same flag.
+        assert_eq!(arg_value(&a, "--crate"), None);
+    }
+
+    #[test]
+    fn target_jobs() {
+        let a = args("rustc --crate-name foo --crate-type lib --target x86_64-unknown-linux-gnu");
+        assert!(!is_host_build(&a, false));
+        assert!(!is_host_build(&a, true));
+        let a = args("rustc --crate-name foo --target=x86_64-unknown-linux-gnu src/lib.rs");
+        assert!(!is_host_build(&a, false));
+    }
+
+    #[test]
+    fn host_jobs_without_target() {
+        // Dependencies of build scripts are built without `--target`.
+        let a = args("rustc --crate-name cc --crate-type lib src/lib.rs");
+        assert!(is_host_build(&a, false));
+        // Packages the user asked for are never host jobs, even without `--target`.
+        assert!(!is_host_build(&a, true));
+    }
+
+    #[test]
+    fn build_scripts_and_proc_macros() {
+        let a = args("rustc --crate-name build_script_build --crate-type bin build.rs \
+            --target x86_64-unknown-linux-gnu");
+        assert!(is_host_build(&a, false));
+        assert!(is_host_build(&a, true));
+        let a = args("rustc --crate-name derive_foo --crate-type proc-macro src/lib.rs");
+        assert!(is_host_build(&a, false));
+        assert!(is_host_build(&a, true));
+        let a = args("rustc --crate-name derive_foo --crate-type=proc-macro \
+            --target x86_64-unknown-linux-gnu");
+        assert!(is_host_build(&a, true));
+    }
+
+    #[test]
+    fn cache_key_inputs() {
+        let dir = temp_dir("cache-key");
+        let src = dir.join("lib.rs");
+        let included = dir.join("data.txt");
+        let dep = dir.join("libdep.rlib");
+        let exe = dir.join("mir-json");
+        for &(ref path, contents) in &[(&src, "fn f() {}"), (&included, "a"), (&dep, "rlib"),
+                (&exe, "exe")] {
+            fs::write(path, contents).unwrap();
+        }
+        let dep_mir = dep.with_extension("mir");
+        let files = vec![src.clone(), included.clone(), dep.clone(), dep_mir.clone()];
+        let a = args("rustc --crate-name foo src/lib.rs");
+        let env_vars = |extra: &[(&str, &str)]| {
+            let mut v = vec![(OsString::from("OUT_DIR"), OsString::from("/out"))];
+            v.extend(extra.iter().map(|&(k, v)| (k.into(), v.into())));
+            v
+        };
+        let key = |a: &[String], env: Vec<(OsString, OsString)>| {
+            mir_cache_key(a, env, &files, &exe).unwrap()
+        };
+
+        let orig = key(&a, env_vars(&[]));
+        assert!(orig.ends_with(".mir"));
+        assert_eq!(key(&a, env_vars(&[])), orig);
+        // Ignored variables don't change the key.
+        assert_eq!(key(&a, env_vars(&[("CARGO_MAKEFLAGS", "-j --jobserver-fds=3,4")])), orig);
+
+        // Each of the inputs does.
+        assert_ne!(key(&args("rustc --crate-name bar src/lib.rs"), env_vars(&[])), orig);
+        assert_ne!(key(&a, env_vars(&[("CRUX_FLAG", "1")])), orig);
+        assert_ne!(key(&a, vec![(OsString::from("OUT_DIR"), OsString::from("/out2"))]), orig);
+        for path in &[&src, &included, &dep, &exe] {
+            let old = fs::read(path).unwrap();
+            fs::write(path, "changed").unwrap();
+            assert_ne!(key(&a, env_vars(&[])), orig, "{}", path.display());
+            fs::write(path, old).unwrap();
+        }
+        fs::write(&dep_mir, "mir").unwrap();
+        assert_ne!(key(&a, env_vars(&[])), orig);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn cache_hit_and_miss() {
+        let dir = temp_dir("cache");
+        let cache = dir.join("cache");
+        let mir_path = dir.join("foo.mir");
+
+        assert!(!restore_cached_mir(&cache, "a.mir", &mir_path).unwrap());
+        assert!(!mir_path.exists());
+
+        fs::write(&mir_path, "exported").unwrap();
+        store_cached_mir(&cache, "a.mir", &mir_path).unwrap();
+        fs::remove_file(&mir_path).unwrap();
+
+        assert!(restore_cached_mir(&cache, "a.mir", &mir_path).unwrap());
+        assert_eq!(fs::read_to_string(&mir_path).unwrap(), "exported");
+        assert!(!restore_cached_mir(&cache, "b.mir", &mir_path).unwrap());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_build_links_unless_no_link() {
+        let dir = temp_dir("test-build");
+        let mir_path = dir.join("foo.mir");
+        let j = crate_json(vec![fn_entry("foo::test", json!("u8"), &[])], &["foo::test"]);
+        lib_util::write_indexed_crate(File::create(&mir_path).unwrap(), &j).unwrap();
+        let test_path = dir.join("foo-1234");
+
+        let opts = TestBuildOptions { no_link: true, ..TestBuildOptions::default() };
+        assert_eq!(link_test_build(&mir_path, &[], &test_path, opts).unwrap(), None);
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 1, "only foo.mir should exist");
+
+        let json_path = link_test_build(&mir_path, &[], &test_path, TestBuildOptions::default())
+            .unwrap().unwrap();
+        let linked: serde_json::Value = serde_json::from_slice(&fs::read(&json_path).unwrap())
+            .unwrap();
+        assert_eq!(linked["roots"], json!(["foo::test"]));
+        assert!(fs::read_dir(&dir).unwrap().count() > 2, "test script should exist");
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
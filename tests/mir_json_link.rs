@@ -0,0 +1,85 @@
+//! Runs the `mir-json-link` binary on `.mir` files written with `lib_util`.
+#[macro_use] extern crate serde_json;
+extern crate mir_json;
+
+use std::env;
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use serde_json::Value as JsonValue;
+use mir_json::lib_util;
+use mir_json::test_fixtures::{crate_json, fn_entry, temp_dir};
+
+/// Cargo puts integration tests in `target/<profile>/deps`, and binaries one level up.
+fn link_exe() -> PathBuf {
+    let mut dir = env::current_exe().unwrap();
+    dir.pop();
+    if dir.ends_with("deps") {
+        dir.pop();
+    }
+    dir.join(format!("mir-json-link{}", env::consts::EXE_SUFFIX))
+}
+
+/// Write `main.mir`, which calls `d::g`, and `dep.mir`, which defines it.
+fn write_inputs(dir: &Path) -> (PathBuf, PathBuf) {
+    let main = crate_json(vec![fn_entry("m::test", json!("u8"), &["d::g"])], &["m::test"]);
+    let dep = crate_json(vec![fn_entry("d::g", json!("u8"), &[])], &[]);
+    let main_path = dir.join("main.mir");
+    let dep_path = dir.join("dep.mir");
+    lib_util::write_indexed_crate(File::create(&main_path).unwrap(), &main).unwrap();
+    lib_util::write_indexed_crate(File::create(&dep_path).unwrap(), &dep).unwrap();
+    (main_path, dep_path)
+}
+
+fn link_cmd() -> Command {
+    let mut cmd = Command::new(link_exe());
+    cmd.env_remove("MIR_JSON_PARTIAL_LINK").env_remove("MIR_JSON_PRUNE");
+    cmd
+}
+
+#[test]
+fn link_and_write_script() {
+    let dir = temp_dir("link-bin-ok");
+    let (main_path, dep_path) = write_inputs(&dir);
+    let out_path = dir.join("out.json");
+    let script_path = dir.join("test-script");
+
+    let output = link_cmd()
+        .arg(&main_path).arg(&dep_path)
+        .arg("-o").arg(&out_path)
+        .arg("--test-script").arg(&script_path)
+        .output().unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+
+    let j: JsonValue = serde_json::from_slice(&fs::read(&out_path).unwrap()).unwrap();
+    let mut names = j["fns"].as_array().unwrap().iter()
+        .map(|f| f["name"].as_str().unwrap().to_owned())
+        .collect::<Vec<_>>();
+    names.sort();
+    assert_eq!(names, ["d::g", "m::test"]);
+    assert_eq!(j["roots"], json!(["m::test"]));
+
+    let script = fs::read_to_string(&script_path).unwrap();
+    assert!(script.contains("out.json"), "{}", script);
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn report_link_errors() {
+    let dir = temp_dir("link-bin-err");
+    let (main_path, _) = write_inputs(&dir);
+    let out_path = dir.join("out.json");
+
+    // Without `dep.mir`, `d::g` is undefined.
+    let output = link_cmd().arg(&main_path).arg("-o").arg(&out_path).output().unwrap();
+    assert_eq!(output.status.code(), Some(1));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("d::g (referenced by m::test)"), "{}", stderr);
+
+    // No output file is a usage error.
+    let output = link_cmd().arg(&main_path).output().unwrap();
+    assert_eq!(output.status.code(), Some(2));
+
+    fs::remove_dir_all(&dir).unwrap();
+}